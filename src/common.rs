@@ -8,7 +8,6 @@ the Apache 2.0 or the MIT license at the licensee's choice. The terms
 and conditions of the chosen license apply to this file.
 */
 
-#[cfg(feature = "image-data")]
 use std::borrow::Cow;
 use thiserror::Error;
 
@@ -49,11 +48,60 @@ pub enum Error {
 	/// This can happen in either of the following cases.
 	///
 	/// - When returned from `set_image`: the image going to the clipboard cannot be converted to the appropriate format.
-	/// - When returned from `get_image`: the image coming from the clipboard could not be converted into the `ImageData` struct.
+	/// - When returned from `get_image`: the image coming from the clipboard could not be converted into the `ImageData` struct,
+	///   for other reasons than the image data being invalid, see [`Error::ImageDecode`].
 	/// - When returned from `get_text`: the text coming from the clipboard is not valid utf-8 or cannot be converted to utf-8.
 	#[error("The image or the text that was about the be transferred to/from the clipboard could not be converted to the appropriate format.")]
 	ConversionFailure,
 
+	/// The clipboard's owner stopped sending its data before all of it arrived.
+	///
+	/// On X11, this happens with large data, which is sent in several chunks. `expected` is the
+	/// minimum size the owner announced up front, so `received` may be smaller than it or, if
+	/// the transfer stalled before finishing, equal to it. Retrying may succeed.
+	///
+	/// On Wayland, this is only returned when `GetExtLinux::verify_wayland_reads` is enabled and
+	/// the reads kept returning different data. `received` and `expected` are then the sizes of
+	/// the shortest and the longest of them.
+	#[error("The clipboard data was only partially received ({received} out of at least {expected} bytes).")]
+	IncompleteTransfer { received: usize, expected: usize },
+
+	/// The operation was cancelled by the caller before it could complete.
+	///
+	/// See `GetExtLinux::cancel_with`.
+	#[error("The clipboard operation was cancelled.")]
+	Cancelled,
+
+	/// The image on the clipboard has more pixels than allowed, so it wasn't decoded.
+	///
+	/// See `Get::max_image_pixels`.
+	#[error("The image on the clipboard is too large to be decoded.")]
+	TooLarge,
+
+	/// The platform-specific type name given to `Get::raw` or `Set::raw` can't name any data.
+	///
+	/// Names must not be empty or contain NUL characters. On Windows, they are also limited to
+	/// 255 UTF-16 code units.
+	#[error("The clipboard data type name is not valid on this platform.")]
+	InvalidContentType,
+
+	/// No clipboard manager took over the clipboard's contents, so they will be gone once this
+	/// process exits.
+	///
+	/// This can only happen on X11, where there may be no clipboard manager running, see
+	/// `ClipboardExtLinux::hand_over`.
+	#[error("No clipboard manager took over the clipboard contents.")]
+	NoClipboardManager,
+
+	/// There is an image on the clipboard, but it couldn't be decoded in any of the formats it's
+	/// offered as.
+	///
+	/// `attempts` lists each format that was tried, in order, together with what went wrong, such
+	/// as `("png", "not present")` or the decoder's error message. Like `Unknown`'s description,
+	/// the messages are meant for diagnostics and shouldn't be matched on.
+	#[error("The image on the clipboard could not be decoded ({}).", describe_attempts(.attempts))]
+	ImageDecode { attempts: Vec<(String, String)> },
+
 	/// Any error that doesn't fit the other error types.
 	///
 	/// The `description` field is only meant to help the developer and should not be relied on as a
@@ -79,12 +127,300 @@ impl std::fmt::Debug for Error {
 			ClipboardNotSupported,
 			ClipboardOccupied,
 			ConversionFailure,
+			IncompleteTransfer { .. },
+			Cancelled,
+			TooLarge,
+			InvalidContentType,
+			NoClipboardManager,
+			ImageDecode { .. },
 			Unknown { .. }
 		);
 		f.write_fmt(format_args!("{} - \"{}\"", name, self))
 	}
 }
 
+/// Lists the formats an image was tried in, as in `png: not present; tiff: invalid header`.
+fn describe_attempts(attempts: &[(String, String)]) -> String {
+	let attempts: Vec<String> =
+		attempts.iter().map(|(format, outcome)| format!("{}: {}", format, outcome)).collect();
+	attempts.join("; ")
+}
+
+/// A line ending style that text can be converted to.
+///
+/// See [`Clipboard::get_text_normalized`](crate::Clipboard::get_text_normalized) and
+/// [`Clipboard::set_text_normalized`](crate::Clipboard::set_text_normalized).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Newline {
+	/// Unix style line endings (`\n`), as used on Linux and macOS.
+	Lf,
+	/// Windows style line endings (`\r\n`).
+	CrLf,
+	/// The line endings used by the platform the program was compiled for.
+	Native,
+}
+
+impl Newline {
+	/// Converts every line ending in `text`, be it `\n`, `\r\n` or a lone `\r`, to this style.
+	pub(crate) fn normalize(self, text: Cow<'_, str>) -> Cow<'_, str> {
+		let newline = match self {
+			Newline::Lf => "\n",
+			Newline::CrLf => "\r\n",
+			Newline::Native if cfg!(windows) => "\r\n",
+			Newline::Native => "\n",
+		};
+		if !text.contains('\r') && (newline == "\n" || !text.contains('\n')) {
+			return text;
+		}
+
+		let mut normalized = String::with_capacity(text.len());
+		let mut chars = text.chars().peekable();
+		while let Some(c) = chars.next() {
+			match c {
+				'\r' => {
+					chars.next_if_eq(&'\n');
+					normalized.push_str(newline);
+				}
+				'\n' => normalized.push_str(newline),
+				c => normalized.push(c),
+			}
+		}
+		Cow::Owned(normalized)
+	}
+}
+
+/// What to remove from text with
+/// [`Clipboard::set_text_sanitized`](crate::Clipboard::set_text_sanitized).
+///
+/// Text copied from terminals may contain escape sequences and control characters, which many
+/// applications show as garbage or act upon when the text is pasted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SanitizePolicy {
+	/// Removes the C0 control characters (`U+0000` to `U+001F`) except for the tab (`U+0009`) and
+	/// the line feed (`U+000A`), as well as delete (`U+007F`) and the C1 control characters
+	/// (`U+0080` to `U+009F`).
+	///
+	/// Carriage returns (`U+000D`) are removed as well, so `\r\n` line endings become `\n`. The
+	/// rest of an escape sequence is left in place: `ESC [ 1 m` turns into `[1m`.
+	ControlCharacters,
+	/// Removes ANSI escape sequences as described by ECMA-48, which are the ones terminals use
+	/// for colors, cursor movement and window titles:
+	///
+	/// - Control sequences, started by `ESC [` or `U+009B`, including their parameters and final
+	///   character, like `ESC [ 3 1 m`.
+	/// - Control strings, started by `ESC ]`, `ESC P`, `ESC X`, `ESC ^` or `ESC _`, up to and
+	///   including the `ESC \`, `U+009C` or `BEL` (`U+0007`) that ends them.
+	/// - Any other `ESC`, together with the characters from `U+0020` to `U+002F` and the single
+	///   character from `U+0030` to `U+007E` following it, like `ESC ( B`.
+	///
+	/// Other control characters are kept.
+	EscapeSequences,
+	/// Removes escape sequences like [`EscapeSequences`](Self::EscapeSequences), and then the
+	/// remaining control characters like [`ControlCharacters`](Self::ControlCharacters).
+	All,
+}
+
+impl SanitizePolicy {
+	/// Removes the characters this policy applies to from `text`.
+	pub(crate) fn sanitize(self, text: Cow<'_, str>) -> Cow<'_, str> {
+		const ESC: char = '\u{1B}';
+		const CSI: char = '\u{9B}';
+
+		let strip_controls = matches!(self, Self::ControlCharacters | Self::All);
+		let strip_escapes = matches!(self, Self::EscapeSequences | Self::All);
+		let is_stripped_control = |c: char| c.is_control() && c != '\t' && c != '\n';
+
+		// Every escape sequence starts with a control character, so text without any is clean.
+		if !text.chars().any(is_stripped_control) {
+			return text;
+		}
+
+		let mut sanitized = String::with_capacity(text.len());
+		let mut chars = text.chars().peekable();
+		while let Some(c) = chars.next() {
+			match c {
+				ESC | CSI if strip_escapes => skip_escape_sequence(c == CSI, &mut chars),
+				c if strip_controls && is_stripped_control(c) => {}
+				c => sanitized.push(c),
+			}
+		}
+		Cow::Owned(sanitized)
+	}
+}
+
+/// Skips the rest of an escape sequence whose `ESC` or `CSI` was just read.
+///
+/// See: https://ecma-international.org/publications-and-standards/standards/ecma-48/
+fn skip_escape_sequence(is_csi: bool, chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+	let in_range = |start: char, end: char| move |c: &char| (start..=end).contains(c);
+
+	if is_csi || chars.next_if_eq(&'[').is_some() {
+		// Parameter and intermediate characters, followed by the final character.
+		while chars.next_if(in_range('\u{20}', '\u{3F}')).is_some() {}
+		chars.next_if(in_range('\u{40}', '\u{7E}'));
+	} else if chars.next_if(|c| matches!(c, ']' | 'P' | 'X' | '^' | '_')).is_some() {
+		// Control strings may contain anything up to their terminator.
+		while let Some(c) = chars.next() {
+			match c {
+				'\u{07}' | '\u{9C}' => break,
+				'\u{1B}' => {
+					chars.next_if_eq(&'\\');
+					break;
+				}
+				_ => {}
+			}
+		}
+	} else {
+		while chars.next_if(in_range('\u{20}', '\u{2F}')).is_some() {}
+		chars.next_if(in_range('\u{30}', '\u{7E}'));
+	}
+}
+
+/// Refuses type names that no platform could register or look up data by.
+pub(crate) fn check_raw_type(system_type: &str) -> Result<(), Error> {
+	if system_type.is_empty() || system_type.contains('\0') {
+		return Err(Error::InvalidContentType);
+	}
+	Ok(())
+}
+
+/// Reports text that is present but empty as missing, if `empty_unavailable` is set.
+pub(crate) fn reject_empty_text(text: String, empty_unavailable: bool) -> Result<String, Error> {
+	if empty_unavailable && text.is_empty() {
+		return Err(Error::ContentNotAvailable);
+	}
+	Ok(text)
+}
+
+/// Removes a leading byte order mark from `text`.
+///
+/// It carries no meaning in UTF-8 and would otherwise show up as an invisible character at the
+/// start of the text. Some applications, mostly on Windows, still add one.
+pub(crate) fn strip_bom(text: Cow<'_, str>) -> Cow<'_, str> {
+	const BOM: char = '\u{FEFF}';
+
+	match text {
+		Cow::Borrowed(text) => Cow::Borrowed(text.strip_prefix(BOM).unwrap_or(text)),
+		Cow::Owned(mut text) => {
+			if text.starts_with(BOM) {
+				text.drain(..BOM.len_utf8());
+			}
+			Cow::Owned(text)
+		}
+	}
+}
+
+/// Derives a plain-text version of `html` by dropping its markup.
+///
+/// This is not a full HTML renderer: tags and comments are removed, the contents of `script` and
+/// `style` elements are skipped, line breaks are inserted around block elements and the most
+/// common character references are decoded.
+pub(crate) fn html_to_text(html: &str) -> String {
+	const BLOCK_ELEMENTS: &[&str] = &[
+		"address",
+		"blockquote",
+		"div",
+		"dl",
+		"dt",
+		"dd",
+		"h1",
+		"h2",
+		"h3",
+		"h4",
+		"h5",
+		"h6",
+		"hr",
+		"li",
+		"ol",
+		"p",
+		"pre",
+		"table",
+		"tr",
+		"ul",
+	];
+
+	let mut text = String::with_capacity(html.len());
+	let mut rest = html;
+	while let Some(start) = rest.find(['<', '&']) {
+		text.push_str(&rest[..start]);
+		rest = &rest[start..];
+
+		if rest.starts_with("<!--") {
+			rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+		} else if rest.starts_with('<') {
+			let end = match rest.find('>') {
+				Some(end) => end,
+				None => break,
+			};
+			let tag = &rest[1..end];
+			rest = &rest[end + 1..];
+
+			let closing = tag.starts_with('/');
+			let name = tag
+				.trim_start_matches('/')
+				.split(|c: char| c.is_ascii_whitespace() || c == '/')
+				.next()
+				.unwrap_or_default()
+				.to_ascii_lowercase();
+			match name.as_str() {
+				"script" | "style" if !closing => {
+					// The closing tag is searched for in place, as the HTML may hold many scripts.
+					let end = rest.match_indices("</").map(|(i, _)| i).find(|&i| {
+						rest[i + 2..]
+							.get(..name.len())
+							.is_some_and(|n| n.eq_ignore_ascii_case(&name))
+					});
+					rest = end.map_or("", |i| &rest[i..]);
+				}
+				"br" => text.push('\n'),
+				name if BLOCK_ELEMENTS.contains(&name)
+					&& !text.is_empty()
+					&& !text.ends_with('\n') =>
+				{
+					text.push('\n');
+				}
+				_ => {}
+			}
+		} else {
+			let entity = rest[1..].find(';').filter(|&end| end <= 8).map(|end| &rest[1..end + 1]);
+			let decoded = entity.and_then(|entity| match entity {
+				"amp" => Some('&'),
+				"lt" => Some('<'),
+				"gt" => Some('>'),
+				"quot" => Some('"'),
+				"apos" => Some('\''),
+				"nbsp" => Some(' '),
+				_ => {
+					let code = entity.strip_prefix('#')?;
+					let code = match code.strip_prefix(|c| c == 'x' || c == 'X') {
+						Some(hex) => u32::from_str_radix(hex, 16),
+						None => code.parse(),
+					};
+					code.ok().and_then(char::from_u32)
+				}
+			});
+			match (entity, decoded) {
+				(Some(entity), Some(c)) => {
+					text.push(c);
+					rest = &rest[entity.len() + 2..];
+				}
+				_ => {
+					text.push('&');
+					rest = &rest[1..];
+				}
+			}
+		}
+	}
+	text.push_str(rest);
+
+	let trimmed = text.trim();
+	if trimmed.len() == text.len() {
+		text
+	} else {
+		trimmed.to_owned()
+	}
+}
+
 /// Stores pixel data of an image.
 ///
 /// Each element in `bytes` stores the value of a channel of a single pixel.
@@ -98,7 +434,6 @@ impl std::fmt::Debug for Error {
 /// Assigning a `2*1` image would for example look like this
 /// ```
 /// use arboard::ImageData;
-/// use std::borrow::Cow;
 /// let bytes = [
 ///     // A red pixel
 ///     255, 0, 0, 255,
@@ -106,12 +441,12 @@ impl std::fmt::Debug for Error {
 ///     // A green pixel
 ///     0, 255, 0, 255,
 /// ];
-/// let img = ImageData {
-///     width: 2,
-///     height: 1,
-///     bytes: Cow::from(bytes.as_ref())
-/// };
+/// let img = ImageData::new(2, 1, bytes.as_ref()).unwrap();
 /// ```
+///
+/// The fields can also be set directly, but prefer [`ImageData::new`] which makes sure that they
+/// agree with each other. Backends may misbehave or even crash when given an image whose `bytes`
+/// don't match its dimensions.
 #[cfg(feature = "image-data")]
 #[derive(Debug, Clone)]
 pub struct ImageData<'a> {
@@ -122,6 +457,23 @@ pub struct ImageData<'a> {
 
 #[cfg(feature = "image-data")]
 impl<'a> ImageData<'a> {
+	/// Creates an image from its dimensions and RGBA pixels.
+	///
+	/// Returns [`Error::ConversionFailure`] if `bytes` doesn't hold exactly `width * height * 4`
+	/// bytes.
+	pub fn new(
+		width: usize,
+		height: usize,
+		bytes: impl Into<Cow<'a, [u8]>>,
+	) -> Result<Self, Error> {
+		let bytes = bytes.into();
+		let expected_len = width.checked_mul(height).and_then(|pixels| pixels.checked_mul(4));
+		if expected_len != Some(bytes.len()) {
+			return Err(Error::ConversionFailure);
+		}
+		Ok(Self { width, height, bytes })
+	}
+
 	/// Returns a the bytes field in a way that it's guaranteed to be owned.
 	/// It moves the bytes if they are already owned and clones them if they are borrowed.
 	pub fn into_owned_bytes(self) -> Cow<'static, [u8]> {
@@ -137,6 +489,187 @@ impl<'a> ImageData<'a> {
 			bytes: self.bytes.clone().into_owned().into(),
 		}
 	}
+
+	/// Returns `true` if both images have the same dimensions and look the same.
+	///
+	/// Unlike comparing `bytes` directly, this is robust against the differences that may be
+	/// introduced by a round-trip through the clipboard on some platforms:
+	///
+	/// - Any padding at the end of each row is ignored.
+	/// - The color channels are compared as if they were premultiplied with the alpha channel,
+	///   allowing a rounding error of one. This means that two fully transparent pixels are
+	///   always considered equal, no matter their color.
+	pub fn pixels_eq(&self, other: &ImageData) -> bool {
+		if self.width != other.width || self.height != other.height {
+			return false;
+		}
+
+		match (self.rows(), other.rows()) {
+			(Some(a), Some(b)) => a.zip(b).all(|(a, b)| {
+				a.chunks_exact(4).zip(b.chunks_exact(4)).all(|(a, b)| premultiplied_eq(a, b))
+			}),
+			_ => false,
+		}
+	}
+
+	/// Returns an iterator over the pixels of each row without any padding, or `None` if
+	/// `bytes` is too short to hold the whole image.
+	fn rows(&self) -> Option<impl Iterator<Item = &[u8]>> {
+		let row_len = self.width * 4;
+		let stride = match self.height {
+			0 => row_len,
+			h => self.bytes.len() / h,
+		};
+		if stride < row_len {
+			return None;
+		}
+
+		Some(self.bytes.chunks(stride.max(1)).take(self.height).map(move |row| &row[..row_len]))
+	}
+}
+
+/// Returns `true` if any of the RGBA `pixels` isn't fully opaque.
+#[cfg(feature = "image-data")]
+pub(crate) fn has_alpha(pixels: &[u8]) -> bool {
+	pixels.chunks_exact(4).any(|pixel| pixel[3] != u8::MAX)
+}
+
+/// Information about an image on the clipboard that is not part of its pixels.
+///
+/// Which fields are available depends on the platform and on the application that placed the
+/// image onto the clipboard.
+#[cfg(feature = "image-data")]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct ImageMetadata {
+	/// The horizontal and vertical resolution of the image, in dots per inch.
+	///
+	/// Together with the pixel dimensions of the image, this gives the logical size the image is
+	/// meant to be displayed at. For example a screenshot taken on a display with a scale factor
+	/// of 2 typically has a resolution of 144 DPI, where 72 DPI corresponds to a scale of 1.
+	pub dpi: Option<(f64, f64)>,
+
+	/// Whether the image actually uses transparency, meaning that at least one of its pixels
+	/// isn't fully opaque.
+	///
+	/// This is always available, since it's found by scanning the pixels.
+	pub has_alpha: bool,
+
+	/// The ICC color profile embedded in the image, if it has one.
+	///
+	/// The bytes are returned as found, without being interpreted, and the pixels are not
+	/// converted according to the profile. Applications that manage color can use this to do so.
+	pub icc_profile: Option<Vec<u8>>,
+}
+
+/// Converts a resolution given in pixels per meter into dots per inch, where zero means unknown.
+#[cfg(all(feature = "image-data", any(windows, all(unix, not(target_os = "macos")))))]
+pub(crate) fn dpi_from_pixels_per_meter(x: u32, y: u32) -> Option<(f64, f64)> {
+	const INCHES_PER_METER: f64 = 1.0 / 0.0254;
+
+	if x == 0 || y == 0 {
+		return None;
+	}
+	Some((f64::from(x) / INCHES_PER_METER, f64::from(y) / INCHES_PER_METER))
+}
+
+/// The number of pixels an image read from the clipboard may have by default, which is 256
+/// megapixels or 1 GiB of RGBA data.
+#[cfg(feature = "image-data")]
+pub(crate) const DEFAULT_MAX_IMAGE_PIXELS: u64 = 256 * 1024 * 1024;
+
+/// Refuses images with more than `max_pixels` pixels, before any memory is allocated for them.
+#[cfg(feature = "image-data")]
+pub(crate) fn check_image_size(width: u64, height: u64, max_pixels: u64) -> Result<(), Error> {
+	match width.checked_mul(height) {
+		Some(pixels) if pixels <= max_pixels => Ok(()),
+		_ => Err(Error::TooLarge),
+	}
+}
+
+/// What [`ImageAttempts`] records for a format that isn't on the clipboard.
+#[cfg(feature = "image-data")]
+const NOT_PRESENT: &str = "not present";
+
+/// Collects the outcome of reading an image in each of the formats it may be offered as, so that
+/// [`Error::ImageDecode`] can tell which were tried when none of them worked.
+#[cfg(feature = "image-data")]
+#[derive(Default)]
+pub(crate) struct ImageAttempts(Vec<(String, String)>);
+
+#[cfg(feature = "image-data")]
+impl ImageAttempts {
+	/// Records the result of reading the image as `format`, returning the image if it was read.
+	///
+	/// Missing or undecodable images are recorded so the next format can be tried, while other
+	/// errors, like [`Error::TooLarge`], are returned right away.
+	pub(crate) fn record<T>(
+		&mut self,
+		format: &str,
+		result: Result<T, Error>,
+	) -> Result<Option<T>, Error> {
+		let outcome = match result {
+			Ok(image) => return Ok(Some(image)),
+			Err(Error::ContentNotAvailable) => NOT_PRESENT.to_owned(),
+			Err(Error::ConversionFailure) => "unsupported image data".to_owned(),
+			Err(Error::ImageDecode { attempts }) => {
+				self.0.extend(attempts);
+				return Ok(None);
+			}
+			Err(e) => return Err(e),
+		};
+		self.0.push((format.to_owned(), outcome));
+		Ok(None)
+	}
+
+	/// Returns the error for when no format worked, which is [`Error::ContentNotAvailable`] if there
+	/// was no image at all.
+	pub(crate) fn into_error(self) -> Error {
+		if self.0.iter().all(|(_, outcome)| outcome == NOT_PRESENT) {
+			Error::ContentNotAvailable
+		} else {
+			Error::ImageDecode { attempts: self.0 }
+		}
+	}
+}
+
+/// Decodes an image of the given format into 8-bit RGBA, after checking its dimensions.
+///
+/// Invalid images are reported as [`Error::ImageDecode`] with the decoder's error message.
+#[cfg(all(feature = "image-data", unix))]
+pub(crate) fn decode_rgba(
+	bytes: &[u8],
+	format: image::ImageFormat,
+	max_pixels: u64,
+) -> Result<ImageData<'static>, Error> {
+	let reader = || image::io::Reader::with_format(std::io::Cursor::new(bytes), format);
+	let decode_error = |e: image::ImageError| Error::ImageDecode {
+		attempts: vec![(format!("{:?}", format).to_lowercase(), e.to_string())],
+	};
+
+	// Only the header is read to find the dimensions, so a small file that decodes to a huge
+	// image is refused before its pixels are.
+	let (width, height) = reader().into_dimensions().map_err(decode_error)?;
+	check_image_size(width.into(), height.into(), max_pixels)?;
+
+	let image = reader().decode().map_err(decode_error)?.into_rgba8();
+	let (width, height) = image.dimensions();
+	Ok(ImageData { width: width as usize, height: height as usize, bytes: image.into_raw().into() })
+}
+
+/// Compares two RGBA pixels as if their color channels were premultiplied with their alpha.
+#[cfg(feature = "image-data")]
+fn premultiplied_eq(a: &[u8], b: &[u8]) -> bool {
+	let (alpha_a, alpha_b) = (u16::from(a[3]), u16::from(b[3]));
+	if alpha_a != alpha_b {
+		return false;
+	}
+
+	a[..3].iter().zip(&b[..3]).all(|(&a, &b)| {
+		let a = (u16::from(a) * alpha_a + 127) / 255;
+		let b = (u16::from(b) * alpha_b + 127) / 255;
+		a.abs_diff(b) <= 1
+	})
 }
 
 #[cfg(any(windows, all(unix, not(target_os = "macos"))))]
@@ -165,6 +698,7 @@ impl<F: FnOnce()> Drop for ScopeGuard<F> {
 pub(crate) mod private {
 	pub trait Sealed {}
 
+	impl Sealed for crate::Clipboard {}
 	impl Sealed for crate::Get<'_> {}
 	impl Sealed for crate::Set<'_> {}
 	impl Sealed for crate::Clear<'_> {}
@@ -11,9 +11,9 @@ and conditions of the chosen license apply to this file.
 mod common;
 use std::borrow::Cow;
 
-pub use common::Error;
+pub use common::{Error, Newline, SanitizePolicy};
 #[cfg(feature = "image-data")]
-pub use common::ImageData;
+pub use common::{ImageData, ImageMetadata};
 
 mod platform;
 
@@ -21,10 +21,27 @@ mod platform;
 	unix,
 	not(any(target_os = "macos", target_os = "android", target_os = "emscripten")),
 ))]
-pub use platform::{ClearExtLinux, GetExtLinux, LinuxClipboardKind, SetExtLinux};
+pub use platform::{
+	ClearExtLinux, ClipboardExtLinux, GetExtLinux, LinuxClipboardKind, SetExtLinux,
+};
+
+/// The `x11rb` crate, whose connection type is returned by
+/// [`ClipboardExtLinux::x11_connection`].
+///
+/// Use this re-export instead of depending on `x11rb` directly to always get the matching
+/// version. Since it's part of arboard's public API, upgrading `x11rb` to an incompatible version
+/// is a breaking change of arboard.
+#[cfg(all(
+	unix,
+	not(any(target_os = "macos", target_os = "android", target_os = "emscripten")),
+))]
+pub use x11rb;
 
 #[cfg(windows)]
-pub use platform::SetExtWindows;
+pub use platform::{ClipboardExtWindows, RawClipboard, SetExtWindows};
+
+#[cfg(target_os = "macos")]
+pub use platform::ClipboardExtMacOS;
 
 /// The OS independent struct for accessing the clipboard.
 ///
@@ -68,16 +85,119 @@ impl Clipboard {
 		Ok(Clipboard { platform: platform::Clipboard::new()? })
 	}
 
+	/// Returns `true` if the current contents of the clipboard were placed there, or cleared, by
+	/// this instance.
+	///
+	/// This lets applications watching the clipboard ignore their own changes.
+	///
+	/// - On Linux, this checks the selection set with `ClipboardExtLinux::set_default_selection`.
+	///   With X11, every instance in the process counts as this one, as they share the window
+	///   owning the selections. With Wayland this isn't possible to tell and
+	///   [`Error::ClipboardNotSupported`] is returned.
+	/// - On Windows and macOS, any change made since the last one by this instance, including by
+	///   other instances in the same process, makes this return `false`.
+	pub fn owner_is_self(&mut self) -> Result<bool, Error> {
+		self.platform.owner_is_self()
+	}
+
 	/// Fetches utf-8 text from the clipboard and returns it.
+	///
+	/// A leading byte order mark is removed, use [`get_text_raw`](Self::get_text_raw) to keep it.
 	pub fn get_text(&mut self) -> Result<String, Error> {
 		self.get().text()
 	}
 
+	/// Fetches utf-8 text from the clipboard and returns it exactly as it was placed there.
+	pub fn get_text_raw(&mut self) -> Result<String, Error> {
+		self.get().text_raw()
+	}
+
 	/// Places the text onto the clipboard. Any valid utf-8 string is accepted.
+	///
+	/// A leading byte order mark is left out.
 	pub fn set_text<'a, T: Into<Cow<'a, str>>>(&mut self, text: T) -> Result<(), Error> {
 		self.set().text(text)
 	}
 
+	/// Places the text onto the clipboard unless it already holds exactly that text, returning
+	/// whether it was written.
+	///
+	/// This keeps clipboard managers from recording the same entry again, and avoids taking over
+	/// the clipboard on X11 for nothing. A clipboard holding empty text counts as unchanged when
+	/// `text` is empty, while one without any text is always written to.
+	pub fn set_text_if_changed<'a, T: Into<Cow<'a, str>>>(
+		&mut self,
+		text: T,
+	) -> Result<bool, Error> {
+		let text = common::strip_bom(text.into());
+		match self.get_text_raw() {
+			Ok(current) if current == text => return Ok(false),
+			Ok(_) | Err(Error::ContentNotAvailable) => {}
+			Err(e) => return Err(e),
+		}
+		self.set_text(text)?;
+		Ok(true)
+	}
+
+	/// Fetches utf-8 text from the clipboard and converts its line endings to the `newline` style.
+	///
+	/// Applications disagree on which line endings to use, so [`get_text`](Self::get_text) returns
+	/// whatever was placed onto the clipboard without modification.
+	pub fn get_text_normalized(&mut self, newline: Newline) -> Result<String, Error> {
+		let text = self.get_text()?;
+		Ok(newline.normalize(Cow::Owned(text)).into_owned())
+	}
+
+	/// Fetches utf-8 text from the clipboard together with the language it is in, as a BCP-47
+	/// tag like `en-US`, if the platform provides that information.
+	///
+	/// See [`Get::text_with_locale`] for details.
+	pub fn get_text_with_locale(&mut self) -> Result<(String, Option<String>), Error> {
+		self.get().text_with_locale()
+	}
+
+	/// Fetches the data stored under the given platform-specific type, exactly as it is.
+	///
+	/// See [`Get::raw`] for what the type refers to on each platform.
+	pub fn get_content_for_raw_type(&mut self, system_type: &str) -> Result<Vec<u8>, Error> {
+		self.get().raw(system_type)
+	}
+
+	/// Places `data` onto the clipboard under the given platform-specific type, exactly as it is.
+	///
+	/// See [`Set::raw`] for what the type refers to on each platform.
+	pub fn set_content_for_raw_type(
+		&mut self,
+		system_type: &str,
+		data: Vec<u8>,
+	) -> Result<(), Error> {
+		self.set().raw(system_type, data)
+	}
+
+	/// Places the text onto the clipboard after converting its line endings to the `newline`
+	/// style.
+	pub fn set_text_normalized<'a, T: Into<Cow<'a, str>>>(
+		&mut self,
+		text: T,
+		newline: Newline,
+	) -> Result<(), Error> {
+		self.set_text(newline.normalize(text.into()))
+	}
+
+	/// Places the text onto the clipboard after removing the control characters and/or escape
+	/// sequences selected by `policy`.
+	///
+	/// This is meant for text coming from terminals or other program output, see
+	/// [`SanitizePolicy`] for exactly what's removed. [`set_text`](Self::set_text) always places
+	/// text as-is.
+	pub fn set_text_sanitized<'a, T: Into<Cow<'a, str>>>(
+		&mut self,
+		text: T,
+		policy: SanitizePolicy,
+	) -> Result<(), Error> {
+		self.set_text(policy.sanitize(text.into()))
+	}
+
 	/// Places the HTML as well as a plain-text alternative onto the clipboard.
 	///
 	/// Any valid utf-8 string is accepted.
@@ -99,6 +219,29 @@ impl Clipboard {
 		self.get().image()
 	}
 
+	/// Fetches image data from the clipboard, and returns the decoded pixels together with the
+	/// image's metadata, such as its resolution.
+	///
+	/// See [`get_image`](Self::get_image) for which images are supported.
+	#[cfg(feature = "image-data")]
+	pub fn get_image_with_metadata(
+		&mut self,
+	) -> Result<(ImageData<'static>, ImageMetadata), Error> {
+		self.get().image_with_metadata()
+	}
+
+	/// Fetches image data from the clipboard, and returns the decoded pixels together with the ICC
+	/// color profile embedded in the image, or `None` if it doesn't have one.
+	///
+	/// The profile is returned as raw bytes and isn't applied to the pixels. This is a shorthand
+	/// for reading [`ImageMetadata::icc_profile`] from
+	/// [`get_image_with_metadata`](Self::get_image_with_metadata).
+	#[cfg(feature = "image-data")]
+	pub fn get_image_with_icc(&mut self) -> Result<(ImageData<'static>, Option<Vec<u8>>), Error> {
+		let (image, metadata) = self.get().image_with_metadata()?;
+		Ok((image, metadata.icc_profile))
+	}
+
 	/// Places an image to the clipboard.
 	///
 	/// The chosen output format, depending on the platform is the following:
@@ -106,6 +249,10 @@ impl Clipboard {
 	/// - On macOS: `NSImage` object
 	/// - On Linux: PNG, under the atom `image/png`
 	/// - On Windows: In order of priority `CF_DIB` and `CF_BITMAP`
+	///
+	/// On X11, the PNG is only encoded when the image is first requested, which moves that work
+	/// from this call to the first paste. Reading the image back from the same process still
+	/// returns it right after this call, or [`Error::ConversionFailure`] if it couldn't be encoded.
 	#[cfg(feature = "image-data")]
 	pub fn set_image(&mut self, image: ImageData) -> Result<(), Error> {
 		self.set().image(image)
@@ -124,12 +271,17 @@ impl Clipboard {
 
 	/// Begins a "get" operation to retrieve data from the clipboard.
 	pub fn get(&mut self) -> Get<'_> {
-		Get { platform: platform::Get::new(&mut self.platform) }
+		Get {
+			platform: platform::Get::new(&mut self.platform),
+			empty_text_unavailable: false,
+			#[cfg(feature = "image-data")]
+			max_image_pixels: common::DEFAULT_MAX_IMAGE_PIXELS,
+		}
 	}
 
 	/// Begins a "set" operation to set the clipboard's contents.
 	pub fn set(&mut self) -> Set<'_> {
-		Set { platform: platform::Set::new(&mut self.platform) }
+		Set { platform: platform::Set::new(&mut self.platform), alt_text_from_html: false }
 	}
 }
 
@@ -137,12 +289,73 @@ impl Clipboard {
 #[must_use]
 pub struct Get<'clipboard> {
 	pub(crate) platform: platform::Get<'clipboard>,
+	empty_text_unavailable: bool,
+	#[cfg(feature = "image-data")]
+	max_image_pixels: u64,
 }
 
 impl Get<'_> {
 	/// Completes the "get" operation by fetching UTF-8 text from the clipboard.
+	///
+	/// A leading byte order mark, which some applications add, is removed.
+	///
+	/// Text that was copied but is empty is returned as an empty string, while a clipboard
+	/// without any text returns [`Error::ContentNotAvailable`]. Use
+	/// [`Get::empty_text_unavailable`] to treat both the same.
 	pub fn text(self) -> Result<String, Error> {
-		self.platform.text()
+		let text = self.platform.text()?;
+		let text = common::strip_bom(Cow::Owned(text)).into_owned();
+		common::reject_empty_text(text, self.empty_text_unavailable)
+	}
+
+	/// Completes the "get" operation by fetching UTF-8 text from the clipboard, without removing
+	/// a leading byte order mark like [`Get::text`] does.
+	pub fn text_raw(self) -> Result<String, Error> {
+		let text = self.platform.text()?;
+		common::reject_empty_text(text, self.empty_text_unavailable)
+	}
+
+	/// Makes reading text that is present but empty return [`Error::ContentNotAvailable`], the
+	/// same as when there is no text at all. This is disabled by default.
+	///
+	/// This is useful for applications that have no use for empty text, such as those showing
+	/// a preview of the clipboard's contents.
+	pub fn empty_text_unavailable(mut self, enabled: bool) -> Self {
+		self.empty_text_unavailable = enabled;
+		self
+	}
+
+	/// Completes the "get" operation by fetching UTF-8 text from the clipboard together with a
+	/// best-effort hint of the language it is in, as a BCP-47 tag like `en-US`.
+	///
+	/// Only Windows provides such a hint, through the `CF_LOCALE` format, and even there many
+	/// applications don't set it. On every other platform the language is always `None`.
+	pub fn text_with_locale(self) -> Result<(String, Option<String>), Error> {
+		let (text, locale) = self.platform.text_with_locale()?;
+		let text = common::strip_bom(Cow::Owned(text)).into_owned();
+		Ok((common::reject_empty_text(text, self.empty_text_unavailable)?, locale))
+	}
+
+	/// Completes the "get" operation by fetching the data stored under `system_type` on the
+	/// clipboard, without any conversion.
+	///
+	/// The type is used as-is, which means it refers to:
+	///
+	/// - On macOS: A pasteboard type, usually a UTI like `public.utf8-plain-text`
+	/// - On Linux: A target atom or MIME type, like `text/uri-list`
+	/// - On Windows: The name of a registered clipboard format, like `HTML Format`. The predefined
+	///   `CF_*` formats don't have names and can't be read this way.
+	///
+	/// This also gives access to images exactly as they were placed, such as the original TIFF
+	/// file under `public.tiff` on macOS or `image/tiff` on Linux, including any layers and
+	/// metadata that [`Get::image`] doesn't keep.
+	///
+	/// Returns [`Error::ContentNotAvailable`] if the clipboard has no data of exactly this type.
+	///
+	/// Returns [`Error::InvalidContentType`] if the type name can't be used on this platform.
+	pub fn raw(self, system_type: &str) -> Result<Vec<u8>, Error> {
+		common::check_raw_type(system_type)?;
+		self.platform.raw(system_type)
 	}
 
 	/// Completes the "get" operation by fetching image data from the clipboard and returning the
@@ -151,9 +364,36 @@ impl Get<'_> {
 	/// Any image data placed on the clipboard with `set_image` will be possible read back, using
 	/// this function. However it's of not guaranteed that an image placed on the clipboard by any
 	/// other application will be of a supported format.
+	///
+	/// Images are always returned as 8-bit RGBA: those with more bits per channel are scaled down,
+	/// and those without an alpha channel are made fully opaque.
+	///
+	/// With the `image-webp` feature enabled, WebP images are also read on Linux and macOS when
+	/// no other image format is offered.
 	#[cfg(feature = "image-data")]
 	pub fn image(self) -> Result<ImageData<'static>, Error> {
-		self.platform.image()
+		self.platform.image(self.max_image_pixels)
+	}
+
+	/// Sets the largest number of pixels an image may have to be read with [`Get::image`] and
+	/// [`Get::image_with_metadata`]. Defaults to 256 megapixels.
+	///
+	/// Any application can put data on the clipboard, including small files that decode to huge
+	/// images. The dimensions of an image are checked before its pixels are decoded, and
+	/// [`Error::TooLarge`] is returned when there are more than `max_pixels` of them.
+	#[cfg(feature = "image-data")]
+	pub fn max_image_pixels(mut self, max_pixels: u64) -> Self {
+		self.max_image_pixels = max_pixels;
+		self
+	}
+
+	/// Completes the "get" operation by fetching image data from the clipboard and returning the
+	/// decoded pixels together with the image's metadata, such as its resolution.
+	#[cfg(feature = "image-data")]
+	pub fn image_with_metadata(self) -> Result<(ImageData<'static>, ImageMetadata), Error> {
+		let (image, mut metadata) = self.platform.image_with_metadata(self.max_image_pixels)?;
+		metadata.has_alpha = common::has_alpha(&image.bytes);
+		Ok((image, metadata))
 	}
 }
 
@@ -161,16 +401,28 @@ impl Get<'_> {
 #[must_use]
 pub struct Set<'clipboard> {
 	pub(crate) platform: platform::Set<'clipboard>,
+	alt_text_from_html: bool,
 }
 
 impl Set<'_> {
 	/// Completes the "set" operation by placing text onto the clipboard. Any valid UTF-8 string
-	/// is accepted.
+	/// is accepted, but a leading byte order mark is left out.
 	pub fn text<'a, T: Into<Cow<'a, str>>>(self, text: T) -> Result<(), Error> {
-		let text = text.into();
+		let text = common::strip_bom(text.into());
 		self.platform.text(text)
 	}
 
+	/// Generates the plain-text alternative of HTML from the HTML itself when none is given to
+	/// [`Set::html`], by stripping its tags.
+	///
+	/// Some applications refuse to paste anything unless the clipboard holds plain text, so this
+	/// is useful when no better alternative is at hand. An explicitly provided alternative text
+	/// is always used as-is.
+	pub fn alt_text_from_html(mut self) -> Self {
+		self.alt_text_from_html = true;
+		self
+	}
+
 	/// Completes the "set" operation by placing HTML as well as a plain-text alternative onto the
 	/// clipboard.
 	///
@@ -181,10 +433,26 @@ impl Set<'_> {
 		alt_text: Option<T>,
 	) -> Result<(), Error> {
 		let html = html.into();
-		let alt_text = alt_text.map(|e| e.into());
+		let alt_text = match alt_text {
+			Some(alt_text) => Some(alt_text.into()),
+			None if self.alt_text_from_html => Some(common::html_to_text(&html).into()),
+			None => None,
+		};
 		self.platform.html(html, alt_text)
 	}
 
+	/// Completes the "set" operation by placing `data` onto the clipboard under `system_type`,
+	/// without any conversion.
+	///
+	/// The type is interpreted the same way as in [`Get::raw`]. On Windows, the format name is
+	/// registered if it wasn't already.
+	///
+	/// Returns [`Error::InvalidContentType`] if the type name can't be used on this platform.
+	pub fn raw(self, system_type: &str, data: Vec<u8>) -> Result<(), Error> {
+		common::check_raw_type(system_type)?;
+		self.platform.raw(system_type, data)
+	}
+
 	/// Completes the "set" operation by placing an image onto the clipboard.
 	///
 	/// The chosen output format, depending on the platform is the following:
@@ -192,6 +460,10 @@ impl Set<'_> {
 	/// - On macOS: `NSImage` object
 	/// - On Linux: PNG, under the atom `image/png`
 	/// - On Windows: In order of priority `CF_DIB` and `CF_BITMAP`
+	///
+	/// On X11, the PNG is only encoded when the image is first requested, which moves that work
+	/// from this call to the first paste. Reading the image back from the same process still
+	/// returns it right after this call, or [`Error::ConversionFailure`] if it couldn't be encoded.
 	#[cfg(feature = "image-data")]
 	pub fn image(self, image: ImageData) -> Result<(), Error> {
 		self.platform.image(image)
@@ -253,13 +525,23 @@ mod tests {
 			ctx.set_text(text).unwrap();
 			assert_eq!(ctx.get_text().unwrap(), text);
 
-			ctx.clear().unwrap();
+			// Empty text is still text, unlike a clipboard without any.
+			ctx.set_text("").unwrap();
+			assert_eq!(ctx.get_text().unwrap(), "");
+			assert!(matches!(
+				ctx.get().empty_text_unavailable(true).text(),
+				Err(Error::ContentNotAvailable)
+			));
 
-			match ctx.get_text() {
-				Ok(text) => assert!(text.is_empty()),
-				Err(Error::ContentNotAvailable) => {}
-				Err(e) => panic!("unexpected error: {}", e),
-			};
+			assert!(!ctx.set_text_if_changed("").unwrap());
+			assert!(ctx.set_text_if_changed(text).unwrap());
+			assert!(!ctx.set_text_if_changed(text).unwrap());
+
+			ctx.clear().unwrap();
+			assert!(matches!(ctx.get_text(), Err(Error::ContentNotAvailable)));
+			assert!(ctx.set_text_if_changed("").unwrap());
+			assert_eq!(ctx.get_text().unwrap(), "");
+			ctx.clear().unwrap();
 
 			// confirm it is OK to clear when already empty.
 			ctx.clear().unwrap();
@@ -285,6 +567,80 @@ mod tests {
 			ctx.set_html(html, Some(alt_text)).unwrap();
 			assert_eq!(ctx.get_text().unwrap(), alt_text);
 		}
+		{
+			let mut ctx = Clipboard::new().unwrap();
+			#[cfg(target_os = "macos")]
+			let system_type = "org.arboard.test-data";
+			#[cfg(not(target_os = "macos"))]
+			let system_type = "application/x-arboard-test";
+			let data = vec![0, 1, 2, 254, 255];
+
+			ctx.set_content_for_raw_type(system_type, data.clone()).unwrap();
+			assert_eq!(ctx.get_content_for_raw_type(system_type).unwrap(), data);
+			assert!(matches!(ctx.get_text(), Err(Error::ContentNotAvailable)));
+
+			assert!(matches!(
+				ctx.set_content_for_raw_type("", data.clone()),
+				Err(Error::InvalidContentType)
+			));
+			assert!(matches!(ctx.get_content_for_raw_type(""), Err(Error::InvalidContentType)));
+			assert_eq!(ctx.get_content_for_raw_type(system_type).unwrap(), data);
+		}
+		// An image that's there but can't be decoded is told apart from no image at all.
+		#[cfg(all(
+			feature = "image-data",
+			unix,
+			not(any(target_os = "macos", target_os = "android", target_os = "emscripten")),
+		))]
+		{
+			let mut ctx = Clipboard::new().unwrap();
+
+			ctx.set_content_for_raw_type("image/png", b"not a png".to_vec()).unwrap();
+			match ctx.get_image() {
+				Err(Error::ImageDecode { attempts }) => assert_eq!(attempts[0].0, "png"),
+				other => panic!("unexpected result: {:?}", other.map(|_| ())),
+			}
+		}
+		#[cfg(not(windows))]
+		{
+			let mut ctx = Clipboard::new().unwrap();
+			#[cfg(target_os = "macos")]
+			let system_type = "public.utf8-plain-text";
+			#[cfg(not(target_os = "macos"))]
+			let system_type = "text/plain;charset=utf-8";
+
+			// Text with a byte order mark can only be placed on the clipboard by other means, as
+			// `set_text` leaves it out.
+			ctx.set_content_for_raw_type(system_type, b"\xEF\xBB\xBFabc".to_vec()).unwrap();
+			assert_eq!(ctx.get_text().unwrap(), "abc");
+			assert_eq!(ctx.get_text_raw().unwrap(), "\u{FEFF}abc");
+
+			ctx.set_text("\u{FEFF}abc").unwrap();
+			assert_eq!(ctx.get_text_raw().unwrap(), "abc");
+		}
+		#[cfg(target_os = "macos")]
+		{
+			use crate::ClipboardExtMacOS;
+
+			let mut ctx = Clipboard::new().unwrap();
+			ctx.set_text("state").unwrap();
+			let (change_count, types) = ctx.get_state().unwrap();
+			assert!(types.iter().any(|t| t == "public.utf8-plain-text"));
+			assert_eq!(ctx.get_state().unwrap().0, change_count);
+
+			ctx.set_text("new state").unwrap();
+			assert!(ctx.get_state().unwrap().0 > change_count);
+		}
+		{
+			let mut ctx = Clipboard::new().unwrap();
+			ctx.set_text("owned by us").unwrap();
+
+			match ctx.owner_is_self() {
+				Ok(is_self) => assert!(is_self),
+				Err(Error::ClipboardNotSupported) => {}
+				Err(e) => panic!("unexpected error: {}", e),
+			}
+		}
 		#[cfg(feature = "image-data")]
 		{
 			let mut ctx = Clipboard::new().unwrap();
@@ -309,6 +665,19 @@ mod tests {
 			let got = ctx.get_image().unwrap();
 			assert_eq!(img_data.bytes, got.bytes);
 
+			// The red and blue channels must not be swapped along the way.
+			let red = ImageData { width: 1, height: 1, bytes: [255, 0, 0, 255].as_ref().into() };
+			ctx.set_image(red).unwrap();
+			let (got, metadata) = ctx.get_image_with_metadata().unwrap();
+			assert_eq!(&got.bytes[..4], &[255, 0, 0, 255]);
+			assert!(!metadata.has_alpha);
+			// macOS may attach the profile of the color space it stored the image in.
+			#[cfg(not(target_os = "macos"))]
+			assert_eq!(ctx.get_image_with_icc().unwrap().1, None);
+
+			ctx.set_image(img_data.clone()).unwrap();
+			assert!(ctx.get_image_with_metadata().unwrap().1.has_alpha);
+
 			#[rustfmt::skip]
 			let big_bytes = vec![
 				255, 100, 100, 255,
@@ -324,13 +693,91 @@ mod tests {
 			ctx.set_image(big_img_data).unwrap();
 			let got = ctx.get_image().unwrap();
 			assert_eq!(bytes_cloned.as_slice(), got.bytes.as_ref());
+
+			assert!(matches!(ctx.get().max_image_pixels(5).image(), Err(Error::TooLarge)));
+			assert_eq!(ctx.get().max_image_pixels(6).image().unwrap().bytes, got.bytes);
+		}
+		#[cfg(windows)]
+		{
+			use crate::ClipboardExtWindows;
+			use clipboard_win::formats::CF_UNICODETEXT;
+
+			let mut ctx = Clipboard::new().unwrap();
+
+			// Some applications pad their text with more than a single NUL terminator.
+			{
+				let _open = ctx.open_raw().unwrap();
+				let padded: Vec<u8> =
+					"abc\0\0\0".encode_utf16().flat_map(|c| c.to_ne_bytes()).collect();
+				clipboard_win::raw::set(CF_UNICODETEXT, &padded).unwrap();
+			}
+			assert_eq!(ctx.get_text().unwrap(), "abc");
+
+			{
+				use clipboard_win::formats::CF_LOCALE;
+
+				let _open = ctx.open_raw().unwrap();
+				clipboard_win::raw::set_without_clear(CF_LOCALE, &0x0409u32.to_ne_bytes()).unwrap();
+			}
+			assert_eq!(
+				ctx.get_text_with_locale().unwrap(),
+				("abc".to_owned(), Some("en-US".to_owned()))
+			);
+
+			{
+				use crate::SetExtWindows;
+
+				ctx.set().text_locale(Some(0x0407)).text("äöü").unwrap();
+				assert_eq!(
+					ctx.get_text_with_locale().unwrap(),
+					("äöü".to_owned(), Some("de-DE".to_owned()))
+				);
+				ctx.set().text_locale(None).text("abc").unwrap();
+				assert!(ctx.get_text_with_locale().unwrap().1.is_some());
+			}
+
+			// Some applications only provide device-dependent bitmaps, which have no alpha channel.
+			#[cfg(feature = "image-data")]
+			{
+				use clipboard_win::formats::CF_BITMAP;
+				use winapi::um::{wingdi::CreateBitmap, winuser::SetClipboardData};
+
+				// Blue, green, red and an unused byte per pixel. The unused byte is filled in case
+				// the system converts the bitmap to a DIB, where it would be taken as alpha.
+				let pixels: [u8; 8] = [0, 0, 255, 255, 255, 0, 0, 255];
+				{
+					let _open = ctx.open_raw().unwrap();
+					clipboard_win::raw::empty().unwrap();
+					unsafe {
+						let hbitmap = CreateBitmap(2, 1, 1, 32, pixels.as_ptr() as *const _);
+						assert!(!hbitmap.is_null());
+						assert!(!SetClipboardData(CF_BITMAP, hbitmap as _).is_null());
+					}
+				}
+				let image = ctx.get_image().unwrap();
+				assert_eq!((image.width, image.height), (2, 1));
+				assert_eq!(image.bytes.as_ref(), &[255, 0, 0, 255, 0, 0, 255, 255]);
+			}
+
+			// Pixels are read back as they were written, so premultiplication shows up as-is.
+			#[cfg(feature = "image-data")]
+			{
+				use crate::SetExtWindows;
+
+				let pixel =
+					ImageData { width: 1, height: 1, bytes: [255, 0, 0, 128].as_ref().into() };
+				ctx.set().image(pixel.clone()).unwrap();
+				assert_eq!(ctx.get_image().unwrap().bytes.as_ref(), &[255, 0, 0, 128]);
+				ctx.set().premultiplied_alpha().image(pixel).unwrap();
+				assert_eq!(ctx.get_image().unwrap().bytes.as_ref(), &[128, 0, 0, 128]);
+			}
 		}
 		#[cfg(all(
 			unix,
 			not(any(target_os = "macos", target_os = "android", target_os = "emscripten")),
 		))]
 		{
-			use crate::{LinuxClipboardKind, SetExtLinux};
+			use crate::{ClipboardExtLinux, LinuxClipboardKind, SetExtLinux};
 			use std::sync::atomic::{self, AtomicBool};
 
 			let mut ctx = Clipboard::new().unwrap();
@@ -364,6 +811,200 @@ mod tests {
 				);
 			}
 
+			// Mirroring to the primary selection is only done on X11
+			if !cfg!(feature = "wayland-data-control")
+				|| std::env::var_os("WAYLAND_DISPLAY").is_none()
+			{
+				ctx.set().mirror_primary().text(TEXT3).unwrap();
+				assert_eq!(
+					TEXT3,
+					&ctx.get().clipboard(LinuxClipboardKind::Primary).text().unwrap()
+				);
+				ctx.set().text(TEXT1).unwrap();
+				assert_eq!(
+					TEXT3,
+					&ctx.get().clipboard(LinuxClipboardKind::Primary).text().unwrap()
+				);
+			}
+
+			// Clearing one selection leaves the others alone.
+			ctx.clear_with_clipboard(LinuxClipboardKind::Primary).unwrap();
+			let available = ctx.available_selections().unwrap();
+			assert!(!available.contains(&LinuxClipboardKind::Primary));
+			assert!(available.contains(&LinuxClipboardKind::Clipboard));
+			assert!(matches!(
+				ctx.get().clipboard(LinuxClipboardKind::Primary).text(),
+				Err(Error::ContentNotAvailable)
+			));
+			assert_eq!(TEXT1, &ctx.get().clipboard(LinuxClipboardKind::Clipboard).text().unwrap());
+
+			// The default selection is used by everything that isn't given one.
+			if ctx.set_default_selection(LinuxClipboardKind::Primary).is_ok() {
+				ctx.set_text(TEXT2).unwrap();
+				assert_eq!(TEXT2, ctx.get_text().unwrap());
+				assert_eq!(
+					TEXT2,
+					&ctx.get().clipboard(LinuxClipboardKind::Primary).text().unwrap()
+				);
+				assert_eq!(
+					TEXT1,
+					&ctx.get().clipboard(LinuxClipboardKind::Clipboard).text().unwrap()
+				);
+				ctx.set_default_selection(LinuxClipboardKind::Clipboard).unwrap();
+			}
+
+			// Clipboards of an explicitly named display work like any other.
+			if let Some(display) = std::env::var("DISPLAY").ok().filter(|_| {
+				!cfg!(feature = "wayland-data-control")
+					|| std::env::var_os("WAYLAND_DISPLAY").is_none()
+			}) {
+				// Naming the default display explicitly still shares the instance's window.
+				let mut same = Clipboard::new_for_display(&display).unwrap();
+				same.set_text(TEXT2).unwrap();
+				assert!(ctx.owner_is_self().unwrap());
+				assert_eq!(TEXT2, ctx.get_text().unwrap());
+
+				// Everything below talks to a client of its own, like another application.
+				let separate_client = |display: &str| Clipboard {
+					platform: platform::Clipboard::new_separate_x11_client(display).unwrap(),
+				};
+				let mut other = separate_client(&display);
+				other.set_text(TEXT3).unwrap();
+				assert!(!ctx.owner_is_self().unwrap());
+				assert_eq!(TEXT3, ctx.get_text().unwrap());
+				ctx.set_text(TEXT1).unwrap();
+				assert_eq!(TEXT1, other.get_text().unwrap());
+				for target in ["text/plain;charset=utf-8", "text/plain"] {
+					assert_eq!(TEXT1.as_bytes(), other.get_content_for_raw_type(target).unwrap());
+					assert_eq!(TEXT1.as_bytes(), ctx.get_content_for_raw_type(target).unwrap());
+				}
+
+				// Many reads in a row from another connection all have to get the data.
+				for _ in 0..200 {
+					assert_eq!(TEXT1, other.get_text().unwrap());
+				}
+
+				// The protocol's own targets are offered next to the data.
+				{
+					use x11rb::protocol::xproto::ConnectionExt as _;
+					let (conn, _) = x11rb::connect(Some(&display)).unwrap();
+					let atom = |name: &str| {
+						conn.intern_atom(true, name.as_bytes()).unwrap().reply().unwrap().atom
+					};
+					let targets: Vec<u32> = other
+						.get_content_for_raw_type("TARGETS")
+						.unwrap()
+						.chunks_exact(4)
+						.map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+						.collect();
+					for name in ["TARGETS", "TIMESTAMP", "MULTIPLE", "UTF8_STRING"] {
+						assert!(targets.contains(&atom(name)), "{} is not offered", name);
+					}
+					assert_eq!(other.get_content_for_raw_type("TIMESTAMP").unwrap().len(), 4);
+
+					// The owner itself gets the same answers as other clients.
+					for target in ["TARGETS", "TIMESTAMP"] {
+						assert_eq!(
+							ctx.get_content_for_raw_type(target).unwrap(),
+							other.get_content_for_raw_type(target).unwrap()
+						);
+					}
+				}
+
+				// Handing the contents over only succeeds if a clipboard manager takes them.
+				{
+					use x11rb::protocol::xproto::ConnectionExt as _;
+					let (conn, _) = x11rb::connect(Some(&display)).unwrap();
+					let manager = conn.intern_atom(false, b"CLIPBOARD_MANAGER").unwrap();
+					let manager = manager.reply().unwrap().atom;
+					let has_manager =
+						conn.get_selection_owner(manager).unwrap().reply().unwrap().owner != 0;
+
+					other.set_text(TEXT2).unwrap();
+					match other.hand_over(Duration::from_millis(500)) {
+						Ok(()) => assert!(has_manager),
+						Err(Error::NoClipboardManager) => {}
+						Err(e) => panic!("unexpected error: {}", e),
+					}
+					assert_eq!(TEXT2, ctx.get_text().unwrap());
+				}
+
+				// Writers racing for the selection are told when they didn't end up owning it.
+				let racer = thread::spawn({
+					let mut racer = separate_client(&display);
+					move || {
+						for _ in 0..50 {
+							match racer.set_text(TEXT3) {
+								Ok(()) | Err(Error::ClipboardOccupied) => {}
+								Err(e) => panic!("unexpected error: {}", e),
+							}
+						}
+					}
+				});
+				for _ in 0..50 {
+					match ctx.set_text(TEXT1) {
+						Ok(()) | Err(Error::ClipboardOccupied) => {}
+						Err(e) => panic!("unexpected error: {}", e),
+					}
+				}
+				racer.join().unwrap();
+				let text = other.get_text().unwrap();
+				assert!(text == TEXT1 || text == TEXT3);
+
+				// Text that doesn't fit into a single request is sent in chunks.
+				let huge = "0123456789abcdef\n".repeat(5 * 1024 * 1024 / 17);
+				other.set_text(huge.as_str()).unwrap();
+				assert!(ctx.get_text().unwrap() == huge);
+				ctx.set_text(huge.as_str()).unwrap();
+				assert!(other.get_text().unwrap() == huge);
+			}
+			assert!(Clipboard::new_for_display(":4242").is_err());
+
+			// Text only in the primary selection can be read with the fallback enabled.
+			if !cfg!(feature = "wayland-data-control")
+				|| std::env::var_os("WAYLAND_DISPLAY").is_none()
+			{
+				ctx.clear().unwrap();
+				ctx.set().clipboard(LinuxClipboardKind::Primary).text(TEXT2).unwrap();
+				assert!(matches!(ctx.get_text(), Err(Error::ContentNotAvailable)));
+				assert_eq!(TEXT2, ctx.get().clipboard_primary_fallback(true).text().unwrap());
+
+				ctx.set_text("").unwrap();
+				assert_eq!("", ctx.get().clipboard_primary_fallback(true).text().unwrap());
+			}
+
+			// Acquiring a selection with a timestamp older than its last change is refused.
+			if !cfg!(feature = "wayland-data-control")
+				|| std::env::var_os("WAYLAND_DISPLAY").is_none()
+			{
+				ctx.clear_with_clipboard(LinuxClipboardKind::Secondary).unwrap();
+				assert!(matches!(
+					ctx.set().clipboard(LinuxClipboardKind::Secondary).time(1).text(TEXT1),
+					Err(Error::ClipboardOccupied)
+				));
+
+				// An older time while owning the selection replaces the contents, but not the
+				// time the selection was acquired at.
+				ctx.set().clipboard(LinuxClipboardKind::Secondary).text(TEXT1).unwrap();
+				let timestamp =
+					ctx.get().clipboard(LinuxClipboardKind::Secondary).raw("TIMESTAMP").unwrap();
+				let time =
+					u32::from_ne_bytes([timestamp[0], timestamp[1], timestamp[2], timestamp[3]]);
+				ctx.set()
+					.clipboard(LinuxClipboardKind::Secondary)
+					.time(time - 1)
+					.text(TEXT2)
+					.unwrap();
+				assert_eq!(
+					TEXT2,
+					ctx.get().clipboard(LinuxClipboardKind::Secondary).text().unwrap()
+				);
+				assert_eq!(
+					timestamp,
+					ctx.get().clipboard(LinuxClipboardKind::Secondary).raw("TIMESTAMP").unwrap()
+				);
+			}
+
 			let was_replaced = Arc::new(AtomicBool::new(false));
 
 			let setter = thread::spawn({
@@ -411,6 +1052,144 @@ mod tests {
 		}
 	}
 
+	#[cfg(feature = "image-data")]
+	#[test]
+	fn image_pixels_eq() {
+		#[rustfmt::skip]
+		let bytes = [
+			255, 0, 0, 255,
+			10, 20, 30, 0,
+		];
+		let img = ImageData { width: 2, height: 1, bytes: bytes.as_ref().into() };
+
+		// Fully transparent pixels are equal no matter their color, and padding is ignored.
+		#[rustfmt::skip]
+		let padded = [
+			255, 0, 0, 255,
+			0, 0, 0, 0,
+			0, 0, 0, 0,
+		];
+		let other = ImageData { width: 2, height: 1, bytes: padded.as_ref().into() };
+		assert!(img.pixels_eq(&other));
+
+		let other = ImageData { width: 1, height: 2, bytes: bytes.as_ref().into() };
+		assert!(!img.pixels_eq(&other));
+
+		let other = ImageData { width: 2, height: 1, bytes: bytes[..4].as_ref().into() };
+		assert!(!img.pixels_eq(&other));
+
+		#[rustfmt::skip]
+		let different = [
+			254, 0, 0, 254,
+			0, 0, 0, 0,
+		];
+		let other = ImageData { width: 2, height: 1, bytes: different.as_ref().into() };
+		assert!(!img.pixels_eq(&other));
+	}
+
+	#[cfg(feature = "image-data")]
+	#[test]
+	fn image_data_new_validates_length() {
+		assert!(ImageData::new(2, 1, [0u8; 8].as_ref()).is_ok());
+		assert!(matches!(ImageData::new(2, 2, [0u8; 8].as_ref()), Err(Error::ConversionFailure)));
+		assert!(matches!(ImageData::new(1, 1, vec![0; 5]), Err(Error::ConversionFailure)));
+		assert!(matches!(
+			ImageData::new(usize::MAX, 2, [0u8; 8].as_ref()),
+			Err(Error::ConversionFailure)
+		));
+	}
+
+	#[test]
+	fn newline_normalization() {
+		let text = "one\ntwo\r\nthree\rfour";
+
+		assert_eq!(Newline::Lf.normalize(text.into()), "one\ntwo\nthree\nfour");
+		assert_eq!(Newline::CrLf.normalize(text.into()), "one\r\ntwo\r\nthree\r\nfour");
+		assert_eq!(Newline::CrLf.normalize("\r\n\r\n".into()), "\r\n\r\n");
+		assert!(matches!(Newline::Lf.normalize("a\nb".into()), Cow::Borrowed(_)));
+	}
+
+	#[test]
+	fn text_sanitization() {
+		let text =
+			"\u{1B}[1;31merror\u{1B}[0m:\tbad\u{7}\r\n\u{1B}]0;title\u{7}done\u{9B}2K\u{1B}(B";
+
+		assert_eq!(SanitizePolicy::All.sanitize(text.into()), "error:\tbad\ndone",);
+		assert_eq!(
+			SanitizePolicy::EscapeSequences.sanitize(text.into()),
+			"error:\tbad\u{7}\r\ndone"
+		);
+		assert_eq!(
+			SanitizePolicy::ControlCharacters.sanitize(text.into()),
+			"[1;31merror[0m:\tbad\n]0;titledone2K(B"
+		);
+		// Control strings can also be ended by `ESC \`.
+		assert_eq!(SanitizePolicy::All.sanitize("a\u{1B}Pdata\u{1B}\\b".into()), "ab");
+		// A sequence cut short only loses what it's made of.
+		assert_eq!(SanitizePolicy::All.sanitize("a\u{1B}[12".into()), "a");
+		assert_eq!(SanitizePolicy::All.sanitize("a\u{1B}[1é".into()), "aé");
+		assert!(matches!(SanitizePolicy::All.sanitize("a\tb\n".into()), Cow::Borrowed(_)));
+	}
+
+	#[test]
+	fn raw_type_validation() {
+		use crate::common::check_raw_type;
+
+		assert!(check_raw_type("text/uri-list").is_ok());
+		assert!(matches!(check_raw_type(""), Err(Error::InvalidContentType)));
+		assert!(matches!(check_raw_type("text/\0plain"), Err(Error::InvalidContentType)));
+	}
+
+	#[test]
+	#[cfg(feature = "image-data")]
+	fn image_decode_attempts() {
+		use crate::common::ImageAttempts;
+
+		let mut attempts = ImageAttempts::default();
+		assert!(attempts.record::<()>("png", Err(Error::ContentNotAvailable)).unwrap().is_none());
+		assert!(attempts.record::<()>("tiff", Err(Error::ContentNotAvailable)).unwrap().is_none());
+		assert!(matches!(attempts.into_error(), Error::ContentNotAvailable));
+
+		let mut attempts = ImageAttempts::default();
+		assert_eq!(attempts.record("png", Ok(1)).unwrap(), Some(1));
+		assert!(matches!(attempts.record::<()>("png", Err(Error::TooLarge)), Err(Error::TooLarge)));
+
+		let mut attempts = ImageAttempts::default();
+		let invalid = Error::ImageDecode { attempts: vec![("png".into(), "bad header".into())] };
+		assert!(attempts.record::<()>("png", Err(invalid)).unwrap().is_none());
+		assert!(attempts.record::<()>("tiff", Err(Error::ContentNotAvailable)).unwrap().is_none());
+		let error = attempts.into_error();
+		assert_eq!(
+			error.to_string(),
+			"The image on the clipboard could not be decoded (png: bad header; tiff: not present)."
+		);
+	}
+
+	#[test]
+	fn byte_order_mark_removal() {
+		use crate::common::strip_bom;
+
+		assert_eq!(strip_bom("\u{FEFF}abc".into()), "abc");
+		assert_eq!(strip_bom(String::from("\u{FEFF}abc").into()), "abc");
+		assert_eq!(strip_bom("abc\u{FEFF}".into()), "abc\u{FEFF}");
+		assert_eq!(strip_bom("\u{FEFF}\u{FEFF}".into()), "\u{FEFF}");
+		assert_eq!(strip_bom("".into()), "");
+	}
+
+	#[test]
+	fn html_to_alt_text() {
+		use crate::common::html_to_text;
+
+		assert_eq!(html_to_text("<b>hello</b> <i>world</i>!"), "hello world!");
+		assert_eq!(
+			html_to_text("<style>p { color: red; }</style><p>one</p><p>two<br>three</p>"),
+			"one\ntwo\nthree"
+		);
+		assert_eq!(html_to_text("<!-- <b>note</b> -->a &lt;&amp;&gt; b&#33;&#x21;"), "a <&> b!!");
+		assert_eq!(html_to_text("fish & chips &unknown; <"), "fish & chips &unknown; <");
+		assert_eq!(html_to_text("<SCRIPT>a < b</p></Script>x<Style>é</b></STYLE>y<script>z"), "xy");
+	}
+
 	#[test]
 	fn clipboard_trait_consistently() {
 		fn assert_send_sync<T: Send + Sync + 'static>() {}
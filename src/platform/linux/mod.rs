@@ -1,11 +1,18 @@
-use std::borrow::Cow;
+use std::{
+	borrow::Cow,
+	sync::{atomic::AtomicBool, Arc},
+	time::Duration,
+};
 
 #[cfg(feature = "wayland-data-control")]
 use log::{trace, warn};
 
-#[cfg(feature = "image-data")]
-use crate::ImageData;
 use crate::{common::private, Error};
+#[cfg(feature = "image-data")]
+use crate::{
+	common::{decode_rgba, dpi_from_pixels_per_meter, ImageAttempts},
+	ImageData, ImageMetadata,
+};
 
 mod x11;
 
@@ -16,14 +23,22 @@ fn into_unknown<E: std::fmt::Display>(error: E) -> Error {
 	Error::Unknown { description: format!("{}", error) }
 }
 
+/// Encodes the image as a PNG file, using a palette instead of full RGBA colors if `indexed` is
+/// set and the image has few enough colors for one.
 #[cfg(feature = "image-data")]
-fn encode_as_png(image: &ImageData) -> Result<Vec<u8>, Error> {
+fn encode_as_png(image: &ImageData, indexed: bool) -> Result<Vec<u8>, Error> {
 	use image::ImageEncoder as _;
 
 	if image.bytes.is_empty() || image.width == 0 || image.height == 0 {
 		return Err(Error::ConversionFailure);
 	}
 
+	if indexed {
+		if let Some(png_bytes) = encode_as_indexed_png(image)? {
+			return Ok(png_bytes);
+		}
+	}
+
 	let mut png_bytes = Vec::new();
 	let encoder = image::codecs::png::PngEncoder::new(&mut png_bytes);
 	encoder
@@ -38,16 +53,172 @@ fn encode_as_png(image: &ImageData) -> Result<Vec<u8>, Error> {
 	Ok(png_bytes)
 }
 
+/// Encodes an image with at most 256 distinct colors as a palette-based PNG file, which keeps
+/// every color exactly and is usually much smaller than storing each pixel's color on its own.
+///
+/// Returns `None` for images with more colors.
+#[cfg(feature = "image-data")]
+fn encode_as_indexed_png(image: &ImageData) -> Result<Option<Vec<u8>>, Error> {
+	use png::{BitDepth, ColorType};
+	use std::collections::{hash_map::Entry, HashMap};
+
+	let pixels = match image.width.checked_mul(image.height) {
+		Some(pixels) if pixels.checked_mul(4) == Some(image.bytes.len()) => pixels,
+		_ => return Ok(None),
+	};
+
+	let mut palette: Vec<&[u8]> = Vec::new();
+	let mut palette_indices = HashMap::new();
+	let mut indices = Vec::with_capacity(pixels);
+	for color in image.bytes.chunks_exact(4) {
+		let index = match palette_indices.entry(color) {
+			Entry::Occupied(entry) => *entry.get(),
+			Entry::Vacant(entry) => {
+				if palette.len() == 256 {
+					return Ok(None);
+				}
+				palette.push(color);
+				*entry.insert((palette.len() - 1) as u8)
+			}
+		};
+		indices.push(index);
+	}
+
+	// Fewer colors need fewer bits per pixel. Pixels are packed from the most significant bit,
+	// with each row starting on a new byte.
+	let depth = match palette.len() {
+		0..=2 => BitDepth::One,
+		3..=4 => BitDepth::Two,
+		5..=16 => BitDepth::Four,
+		_ => BitDepth::Eight,
+	};
+	let bits = depth as usize;
+	let row_len = (image.width * bits).div_ceil(8);
+	let mut data = vec![0; row_len * image.height];
+	for (row, row_indices) in data.chunks_exact_mut(row_len).zip(indices.chunks_exact(image.width))
+	{
+		for (x, &index) in row_indices.iter().enumerate() {
+			let bit = x * bits;
+			row[bit / 8] |= index << (8 - bits - bit % 8);
+		}
+	}
+
+	let mut png_bytes = Vec::new();
+	let mut encoder = png::Encoder::new(&mut png_bytes, image.width as u32, image.height as u32);
+	encoder.set_color(ColorType::Indexed);
+	encoder.set_depth(depth);
+	encoder.set_palette(palette.iter().flat_map(|color| &color[..3]).copied().collect::<Vec<u8>>());
+	// Alpha values may be left out for the opaque colors at the end of the palette.
+	if let Some(last) = palette.iter().rposition(|color| color[3] != 255) {
+		encoder.set_trns(palette[..=last].iter().map(|color| color[3]).collect::<Vec<u8>>());
+	}
+	let mut writer = encoder.write_header().map_err(|_| Error::ConversionFailure)?;
+	writer.write_image_data(&data).map_err(|_| Error::ConversionFailure)?;
+	writer.finish().map_err(|_| Error::ConversionFailure)?;
+
+	Ok(Some(png_bytes))
+}
+
+/// Decodes a PNG file of any color type and bit depth into 8-bit RGBA.
+///
+/// Palettes are expanded, grayscale is replicated into all three color channels, images without
+/// transparency become fully opaque and 16-bit samples are scaled down to 8 bits.
+#[cfg(feature = "image-data")]
+fn decode_png(png: &[u8], max_pixels: u64) -> Result<(ImageData<'static>, ImageMetadata), Error> {
+	let image_data = decode_rgba(png, image::ImageFormat::Png, max_pixels)?;
+	let metadata = ImageMetadata {
+		dpi: png_dpi(png),
+		icc_profile: png_icc_profile(png),
+		..Default::default()
+	};
+
+	Ok((image_data, metadata))
+}
+
+/// Decodes a WebP image into 8-bit RGBA.
+#[cfg(feature = "image-webp")]
+fn decode_webp(webp: &[u8], max_pixels: u64) -> Result<(ImageData<'static>, ImageMetadata), Error> {
+	let image_data = decode_rgba(webp, image::ImageFormat::WebP, max_pixels)?;
+	Ok((image_data, ImageMetadata::default()))
+}
+
+#[cfg(feature = "image-data")]
+fn read_be_u32(bytes: &[u8]) -> Option<u32> {
+	use std::convert::TryInto;
+
+	Some(u32::from_be_bytes(bytes.get(..4)?.try_into().ok()?))
+}
+
+/// Finds the data of an ancillary chunk that has to appear before the image data in a PNG file.
+///
+/// Checksums aren't verified, since the image itself is decoded separately.
+#[cfg(feature = "image-data")]
+fn find_png_chunk<'a>(png: &'a [u8], wanted: &[u8; 4]) -> Option<&'a [u8]> {
+	const SIGNATURE_LEN: usize = 8;
+
+	let mut chunks = png.get(SIGNATURE_LEN..)?;
+	loop {
+		let len = read_be_u32(chunks)? as usize;
+		let chunk_type = chunks.get(4..8)?;
+		let data = chunks.get(8..8usize.checked_add(len)?)?;
+
+		match chunk_type {
+			_ if chunk_type == wanted => return Some(data),
+			// There's no use in looking past the image data.
+			b"IDAT" | b"IEND" => return None,
+			// Skip the data and the checksum.
+			_ => chunks = chunks.get(8 + len + 4..)?,
+		}
+	}
+}
+
+/// Reads the resolution from the `pHYs` chunk of a PNG file, if it has one.
+///
+/// See: https://www.w3.org/TR/png/#11pHYs
+#[cfg(feature = "image-data")]
+fn png_dpi(png: &[u8]) -> Option<(f64, f64)> {
+	const UNIT_METER: u8 = 1;
+
+	match find_png_chunk(png, b"pHYs")? {
+		data if data.len() == 9 && data[8] == UNIT_METER => {
+			dpi_from_pixels_per_meter(read_be_u32(&data[0..4])?, read_be_u32(&data[4..8])?)
+		}
+		_ => None,
+	}
+}
+
+/// Reads the ICC profile from the `iCCP` chunk of a PNG file, if it has one.
+///
+/// See: https://www.w3.org/TR/png/#11iCCP
+#[cfg(feature = "image-data")]
+fn png_icc_profile(png: &[u8]) -> Option<Vec<u8>> {
+	/// Profiles are rarely more than a few hundred kilobytes, so this only guards against
+	/// decompression bombs.
+	const MAX_PROFILE_LEN: usize = 16 * 1024 * 1024;
+	const COMPRESSION_DEFLATE: u8 = 0;
+
+	let data = find_png_chunk(png, b"iCCP")?;
+	// The profile name is followed by a nul separator and the compression method.
+	let name_end = data.iter().position(|&b| b == 0)?;
+	match data.get(name_end + 1..)? {
+		[COMPRESSION_DEFLATE, profile @ ..] => {
+			miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(profile, MAX_PROFILE_LEN).ok()
+		}
+		_ => None,
+	}
+}
+
 /// Clipboard selection
 ///
 /// Linux has a concept of clipboard "selections" which tend to be used in different contexts. This
 /// enum provides a way to get/set to a specific clipboard (the default
 /// [`Clipboard`](Self::Clipboard) being used for the common platform API). You can choose which
-/// clipboard to use with [`GetExtLinux::clipboard`] and [`SetExtLinux::clipboard`].
+/// clipboard to use with [`GetExtLinux::clipboard`] and [`SetExtLinux::clipboard`], or change the
+/// default with [`ClipboardExtLinux::set_default_selection`].
 ///
 /// See <https://specifications.freedesktop.org/clipboards-spec/clipboards-0.1.txt> for a better
 /// description of the different clipboards.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum LinuxClipboardKind {
 	/// Typically used selection for explicit cut/copy/paste actions (ie. windows/macos like
 	/// clipboard behavior)
@@ -67,7 +238,13 @@ pub enum LinuxClipboardKind {
 	Secondary,
 }
 
-pub(crate) enum Clipboard {
+pub(crate) struct Clipboard {
+	backend: Backend,
+	/// The selection used by operations that weren't given one explicitly.
+	default_selection: LinuxClipboardKind,
+}
+
+enum Backend {
 	X11(x11::Clipboard),
 
 	#[cfg(feature = "wayland-data-control")]
@@ -76,6 +253,34 @@ pub(crate) enum Clipboard {
 
 impl Clipboard {
 	pub(crate) fn new() -> Result<Self, Error> {
+		Ok(Self { backend: Backend::new()?, default_selection: LinuxClipboardKind::Clipboard })
+	}
+
+	pub(crate) fn owner_is_self(&mut self) -> Result<bool, Error> {
+		match &self.backend {
+			Backend::X11(clipboard) => clipboard.is_owner(self.default_selection),
+			// The data is served from another process, which can't be told apart from any other.
+			#[cfg(feature = "wayland-data-control")]
+			Backend::WlDataControl(_) => Err(Error::ClipboardNotSupported),
+		}
+	}
+
+	fn new_for_display(display: &str) -> Result<Self, Error> {
+		let backend = Backend::X11(x11::Clipboard::with_display(Some(display))?);
+		Ok(Self { backend, default_selection: LinuxClipboardKind::Clipboard })
+	}
+
+	/// Connects to the X server as a client of its own, instead of sharing the connection of the
+	/// other instances for `display`.
+	#[cfg(test)]
+	pub(crate) fn new_separate_x11_client(display: &str) -> Result<Self, Error> {
+		let backend = Backend::X11(x11::Clipboard::separate_client(Some(display))?);
+		Ok(Self { backend, default_selection: LinuxClipboardKind::Clipboard })
+	}
+}
+
+impl Backend {
+	fn new() -> Result<Self, Error> {
 		#[cfg(feature = "wayland-data-control")]
 		{
 			if std::env::var_os("WAYLAND_DISPLAY").is_some() {
@@ -96,31 +301,202 @@ impl Clipboard {
 	}
 }
 
+/// Linux-specific extensions to the [`Clipboard`](crate::Clipboard) type.
+pub trait ClipboardExtLinux: private::Sealed {
+	/// Creates an instance of the clipboard that uses the X server of the given display, such as
+	/// `:1`, instead of the one named by the `DISPLAY` environment variable.
+	///
+	/// This always uses the X11 backend, even if the Wayland one is enabled and available. Any
+	/// number of displays can be used at the same time, each with their own clipboards.
+	fn new_for_display(display: &str) -> Result<Self, Error>
+	where
+		Self: Sized;
+
+	/// Returns the X11 connection used by arboard together with the window that owns the
+	/// selections set through arboard, or `None` if the Wayland backend is in use.
+	///
+	/// This allows performing X11 operations arboard doesn't expose without opening another
+	/// connection. The connection is shared with a background thread that serves the clipboard
+	/// contents to other applications, so the following must be upheld:
+	///
+	/// - Never wait for or poll events on this connection, they are consumed by arboard.
+	/// - Never destroy the returned window or change which selections it owns.
+	///
+	/// The connection type comes from the [`x11rb`](crate::x11rb) crate that arboard re-exports.
+	fn x11_connection(&self) -> Option<(&x11rb::rust_connection::RustConnection, u32)>;
+
+	/// Returns the selections which currently have content, in other words the ones some
+	/// application owns.
+	///
+	/// This can for example tell whether middle-click pasting from
+	/// [`Primary`](LinuxClipboardKind::Primary) is possible at the moment. On Wayland,
+	/// [`Secondary`](LinuxClipboardKind::Secondary) is never included since it isn't supported.
+	fn available_selections(&mut self) -> Result<Vec<LinuxClipboardKind>, Error>;
+
+	/// Clears the contents of a single selection, leaving the others untouched.
+	///
+	/// This is a shorthand for `clear_with().clipboard(selection)`, see
+	/// [`ClearExtLinux::clipboard`].
+	fn clear_with_clipboard(&mut self, selection: LinuxClipboardKind) -> Result<(), Error>;
+
+	/// Sets the selection that operations act on when they aren't given one with the
+	/// `clipboard` method of the builders, such as [`GetExtLinux::clipboard`].
+	///
+	/// This is [`LinuxClipboardKind::Clipboard`] by default. Applications that mostly work with
+	/// the [`Primary`](LinuxClipboardKind::Primary) selection, like terminal emulators, can use
+	/// this to have `get_text`, `set_text` and the others use it instead.
+	///
+	/// Returns [`Error::ClipboardNotSupported`] if the selection can't be used with the current
+	/// backend, which on Wayland is the case for
+	/// [`Secondary`](LinuxClipboardKind::Secondary) and for `Primary` if the compositor doesn't
+	/// support it. The default selection is left unchanged in that case.
+	fn set_default_selection(&mut self, selection: LinuxClipboardKind) -> Result<(), Error>;
+
+	/// Hands the contents of the [`Clipboard`](LinuxClipboardKind::Clipboard) selection over to
+	/// the clipboard manager, waiting up to `timeout` for it to save them.
+	///
+	/// On X11, contents are served by the process that placed them, so they are gone once it
+	/// exits unless a clipboard manager took them over. This is attempted when the last
+	/// [`Clipboard`](crate::Clipboard) is dropped, but only for a short moment. Short-lived
+	/// processes, like command line tools that copy something and exit, can call this after
+	/// setting the contents to be sure they persist. Nothing happens if this process doesn't own
+	/// the selection.
+	///
+	/// Returns [`Error::NoClipboardManager`] if there's no clipboard manager, or it didn't save
+	/// the contents in time.
+	///
+	/// On Wayland, contents are served by a background process that outlives this one, so this
+	/// always succeeds right away.
+	fn hand_over(&mut self, timeout: Duration) -> Result<(), Error>;
+}
+
+impl ClipboardExtLinux for crate::Clipboard {
+	fn new_for_display(display: &str) -> Result<Self, Error> {
+		Ok(Self { platform: Clipboard::new_for_display(display)? })
+	}
+
+	fn x11_connection(&self) -> Option<(&x11rb::rust_connection::RustConnection, u32)> {
+		match &self.platform.backend {
+			Backend::X11(clipboard) => Some(clipboard.connection()),
+			#[cfg(feature = "wayland-data-control")]
+			Backend::WlDataControl(_) => None,
+		}
+	}
+
+	fn available_selections(&mut self) -> Result<Vec<LinuxClipboardKind>, Error> {
+		match &mut self.platform.backend {
+			Backend::X11(clipboard) => clipboard.available_selections(),
+			#[cfg(feature = "wayland-data-control")]
+			Backend::WlDataControl(clipboard) => clipboard.available_selections(),
+		}
+	}
+
+	fn clear_with_clipboard(&mut self, selection: LinuxClipboardKind) -> Result<(), Error> {
+		self.clear_with().clipboard(selection)
+	}
+
+	fn set_default_selection(&mut self, selection: LinuxClipboardKind) -> Result<(), Error> {
+		match &self.platform.backend {
+			Backend::X11(_) => {}
+			#[cfg(feature = "wayland-data-control")]
+			Backend::WlDataControl(clipboard) => clipboard.check_supported(selection)?,
+		}
+		self.platform.default_selection = selection;
+		Ok(())
+	}
+
+	fn hand_over(&mut self, timeout: Duration) -> Result<(), Error> {
+		match &self.platform.backend {
+			Backend::X11(clipboard) => clipboard.hand_over(timeout),
+			#[cfg(feature = "wayland-data-control")]
+			Backend::WlDataControl(_) => Ok(()),
+		}
+	}
+}
+
 pub(crate) struct Get<'clipboard> {
 	clipboard: &'clipboard mut Clipboard,
 	selection: LinuxClipboardKind,
+	cancel: Option<Arc<AtomicBool>>,
+	primary_fallback: bool,
+	verify_reads: bool,
 }
 
 impl<'clipboard> Get<'clipboard> {
 	pub(crate) fn new(clipboard: &'clipboard mut Clipboard) -> Self {
-		Self { clipboard, selection: LinuxClipboardKind::Clipboard }
+		let selection = clipboard.default_selection;
+		Self { clipboard, selection, cancel: None, primary_fallback: false, verify_reads: false }
 	}
 
 	pub(crate) fn text(self) -> Result<String, Error> {
-		match self.clipboard {
-			Clipboard::X11(clipboard) => clipboard.get_text(self.selection),
+		match &mut self.clipboard.backend {
+			Backend::X11(clipboard) => {
+				let text = clipboard.get_text(self.selection, self.cancel.as_deref());
+				match text {
+					// Empty text is still text, so only a selection without any falls back.
+					Err(Error::ContentNotAvailable)
+						if self.primary_fallback
+							&& self.selection == LinuxClipboardKind::Clipboard =>
+					{
+						clipboard.get_text(LinuxClipboardKind::Primary, self.cancel.as_deref())
+					}
+					other => other,
+				}
+			}
+			#[cfg(feature = "wayland-data-control")]
+			Backend::WlDataControl(clipboard) => clipboard.get_text(self.selection, self.verify_reads),
+		}
+	}
+
+	pub(crate) fn text_with_locale(self) -> Result<(String, Option<String>), Error> {
+		self.text().map(|text| (text, None))
+	}
+
+	pub(crate) fn raw(self, system_type: &str) -> Result<Vec<u8>, Error> {
+		match &mut self.clipboard.backend {
+			Backend::X11(clipboard) => {
+				clipboard.get_raw(self.selection, system_type, self.cancel.as_deref())
+			}
 			#[cfg(feature = "wayland-data-control")]
-			Clipboard::WlDataControl(clipboard) => clipboard.get_text(self.selection),
+			Backend::WlDataControl(clipboard) => {
+				clipboard.get_raw(self.selection, system_type, self.verify_reads)
+			}
 		}
 	}
 
 	#[cfg(feature = "image-data")]
-	pub(crate) fn image(self) -> Result<ImageData<'static>, Error> {
-		match self.clipboard {
-			Clipboard::X11(clipboard) => clipboard.get_image(self.selection),
+	pub(crate) fn image(self, max_pixels: u64) -> Result<ImageData<'static>, Error> {
+		self.image_with_metadata(max_pixels).map(|(image, _)| image)
+	}
+
+	#[cfg(feature = "image-data")]
+	pub(crate) fn image_with_metadata(
+		self,
+		max_pixels: u64,
+	) -> Result<(ImageData<'static>, ImageMetadata), Error> {
+		let mut attempts = ImageAttempts::default();
+
+		let png = match &mut self.clipboard.backend {
+			Backend::X11(clipboard) => clipboard.get_png(self.selection, self.cancel.as_deref()),
 			#[cfg(feature = "wayland-data-control")]
-			Clipboard::WlDataControl(clipboard) => clipboard.get_image(self.selection),
+			Backend::WlDataControl(clipboard) => clipboard.get_png(self.selection, self.verify_reads),
+		};
+		if let Some(image) =
+			attempts.record("png", png.and_then(|png| decode_png(&png, max_pixels)))?
+		{
+			return Ok(image);
 		}
+
+		// Browsers may only offer WebP when copying such images.
+		#[cfg(feature = "image-webp")]
+		{
+			let webp = self.raw("image/webp").and_then(|webp| decode_webp(&webp, max_pixels));
+			if let Some(image) = attempts.record("webp", webp)? {
+				return Ok(image);
+			}
+		}
+
+		Err(attempts.into_error())
 	}
 }
 
@@ -131,6 +507,56 @@ pub trait GetExtLinux: private::Sealed {
 	/// If wayland support is enabled and available, attempting to use the Secondary clipboard will
 	/// return an error.
 	fn clipboard(self, selection: LinuxClipboardKind) -> Self;
+
+	/// Allows aborting the operation by setting `cancel` to `true`, for example from another
+	/// thread.
+	///
+	/// On X11 reading data means waiting for the application that owns it to send it over, which
+	/// can take several seconds if that application is slow or unresponsive. Once cancelled, the
+	/// operation stops waiting and returns [`Error::Cancelled`].
+	///
+	/// # Examples
+	///
+	/// ```no_run
+	/// use arboard::{Clipboard, GetExtLinux};
+	/// use std::sync::{atomic::AtomicBool, Arc};
+	/// # fn main() -> Result<(), arboard::Error> {
+	/// let mut ctx = Clipboard::new()?;
+	///
+	/// let cancel = Arc::new(AtomicBool::new(false));
+	/// // Hand out a clone of `cancel` to whoever may need to abort the operation.
+	/// let text = ctx.get().cancel_with(cancel.clone()).text()?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	///
+	/// This has no effect when the Wayland backend is in use.
+	fn cancel_with(self, cancel: Arc<AtomicBool>) -> Self;
+
+	/// Whether reading text from the [`Clipboard`](LinuxClipboardKind::Clipboard) selection
+	/// should fall back to the [`Primary`](LinuxClipboardKind::Primary) selection when it
+	/// doesn't contain any text. Defaults to `false`.
+	///
+	/// Some applications only ever set the primary selection, so this lets pasting work with
+	/// them too. Empty text in the clipboard selection is returned as is, without falling back.
+	///
+	/// This only applies to [`text`](crate::Get::text) and has no effect when the Wayland backend
+	/// is in use.
+	fn clipboard_primary_fallback(self, enabled: bool) -> Self;
+
+	/// Whether to read the data repeatedly until two reads in a row return the same bytes.
+	/// Defaults to `false`.
+	///
+	/// On Wayland, the application offering the data may replace or revoke its offer while the
+	/// data is sent, such as when it updates the clipboard at the same time. The data then just
+	/// ends early, which can't be told apart from its actual end, so truncated data is returned
+	/// as if it were complete. Enabling this catches that at the cost of transferring the data
+	/// at least twice. If three reads in a row all differ, [`Error::IncompleteTransfer`] is
+	/// returned. That also happens for applications that produce different data for every
+	/// request, which can then no longer be read with this enabled.
+	///
+	/// This has no effect when the X11 backend is in use.
+	fn verify_wayland_reads(self, enabled: bool) -> Self;
 }
 
 impl GetExtLinux for crate::Get<'_> {
@@ -138,41 +564,109 @@ impl GetExtLinux for crate::Get<'_> {
 		self.platform.selection = selection;
 		self
 	}
+
+	fn cancel_with(mut self, cancel: Arc<AtomicBool>) -> Self {
+		self.platform.cancel = Some(cancel);
+		self
+	}
+
+	fn clipboard_primary_fallback(mut self, enabled: bool) -> Self {
+		self.platform.primary_fallback = enabled;
+		self
+	}
+
+	fn verify_wayland_reads(mut self, enabled: bool) -> Self {
+		self.platform.verify_reads = enabled;
+		self
+	}
 }
 
 pub(crate) struct Set<'clipboard> {
 	clipboard: &'clipboard mut Clipboard,
 	wait: bool,
 	selection: LinuxClipboardKind,
+	owner_name: Option<String>,
+	mirror_primary: bool,
+	time: Option<u32>,
+	indexed_png: bool,
 }
 
 impl<'clipboard> Set<'clipboard> {
 	pub(crate) fn new(clipboard: &'clipboard mut Clipboard) -> Self {
-		Self { clipboard, wait: false, selection: LinuxClipboardKind::Clipboard }
+		let selection = clipboard.default_selection;
+		Self {
+			clipboard,
+			wait: false,
+			selection,
+			owner_name: None,
+			mirror_primary: false,
+			time: None,
+			indexed_png: false,
+		}
+	}
+
+	fn apply_owner_name(&self) -> Result<(), Error> {
+		match (&self.clipboard.backend, &self.owner_name) {
+			(Backend::X11(clipboard), Some(name)) => clipboard.set_owner_name(name),
+			_ => Ok(()),
+		}
 	}
 
 	pub(crate) fn text(self, text: Cow<'_, str>) -> Result<(), Error> {
-		match self.clipboard {
-			Clipboard::X11(clipboard) => clipboard.set_text(text, self.selection, self.wait),
+		self.apply_owner_name()?;
+		match &mut self.clipboard.backend {
+			Backend::X11(clipboard) => {
+				if self.mirror_primary && self.selection != LinuxClipboardKind::Primary {
+					// Only the selection that was asked for is waited on.
+					clipboard.set_text(
+						text.clone(),
+						LinuxClipboardKind::Primary,
+						false,
+						self.time,
+					)?;
+				}
+				clipboard.set_text(text, self.selection, self.wait, self.time)
+			}
 			#[cfg(feature = "wayland-data-control")]
-			Clipboard::WlDataControl(clipboard) => clipboard.set_text(text, self.selection, self.wait),
+			Backend::WlDataControl(clipboard) => clipboard.set_text(text, self.selection, self.wait),
 		}
 	}
 
 	pub(crate) fn html(self, html: Cow<'_, str>, alt: Option<Cow<'_, str>>) -> Result<(), Error> {
-		match self.clipboard {
-			Clipboard::X11(clipboard) => clipboard.set_html(html, alt, self.selection, self.wait),
+		self.apply_owner_name()?;
+		match &mut self.clipboard.backend {
+			Backend::X11(clipboard) => {
+				clipboard.set_html(html, alt, self.selection, self.wait, self.time)
+			}
 			#[cfg(feature = "wayland-data-control")]
-			Clipboard::WlDataControl(clipboard) => clipboard.set_html(html, alt, self.selection, self.wait),
+			Backend::WlDataControl(clipboard) => clipboard.set_html(html, alt, self.selection, self.wait),
+		}
+	}
+
+	pub(crate) fn raw(self, system_type: &str, data: Vec<u8>) -> Result<(), Error> {
+		self.apply_owner_name()?;
+		match &mut self.clipboard.backend {
+			Backend::X11(clipboard) => {
+				clipboard.set_raw(system_type, data, self.selection, self.wait, self.time)
+			}
+			#[cfg(feature = "wayland-data-control")]
+			Backend::WlDataControl(clipboard) => {
+				clipboard.set_raw(system_type, data, self.selection, self.wait)
+			}
 		}
 	}
 
 	#[cfg(feature = "image-data")]
 	pub(crate) fn image(self, image: ImageData<'_>) -> Result<(), Error> {
-		match self.clipboard {
-			Clipboard::X11(clipboard) => clipboard.set_image(image, self.selection, self.wait),
+		self.apply_owner_name()?;
+		match &mut self.clipboard.backend {
+			Backend::X11(clipboard) => {
+				clipboard.set_image(image, self.selection, self.wait, self.time, self.indexed_png)
+			}
 			#[cfg(feature = "wayland-data-control")]
-			Clipboard::WlDataControl(clipboard) => clipboard.set_image(image, self.selection, self.wait),
+			Backend::WlDataControl(clipboard) => {
+				clipboard.set_image(image, self.selection, self.wait, self.indexed_png)
+			}
 		}
 	}
 }
@@ -227,6 +721,47 @@ pub trait SetExtLinux: private::Sealed {
 	/// # }
 	/// ```
 	fn clipboard(self, selection: LinuxClipboardKind) -> Self;
+
+	/// Names the X11 window which owns the data set on the clipboard, through its `WM_NAME`,
+	/// `_NET_WM_NAME` and `WM_CLASS` properties.
+	///
+	/// Some desktops and clipboard managers use these to show which application the clipboard's
+	/// contents were copied from. By default the window has no name. All clipboards in a process
+	/// share the same window, so the name stays in effect until another one is set.
+	///
+	/// This has no effect when the Wayland backend is in use.
+	fn owner_name(self, name: &str) -> Self;
+
+	/// Also places text onto the [`Primary`](LinuxClipboardKind::Primary) selection when setting
+	/// it to another one.
+	///
+	/// This saves a separate call for applications which want pasting with the middle mouse
+	/// button to work right after copying. Only text is mirrored, and when combined with
+	/// [`wait`](Self::wait), only the selection chosen with [`clipboard`](Self::clipboard) is
+	/// waited on.
+	///
+	/// This has no effect when the Wayland backend is in use.
+	fn mirror_primary(self) -> Self;
+
+	/// Acquires the selection at the given X server timestamp, instead of the current server
+	/// time.
+	///
+	/// ICCCM asks clients to use the timestamp of the event that triggered the change, such as a
+	/// key press, which matters when events are handled late or replayed. If the X server refuses
+	/// the timestamp, because it's older than the one the selection was last acquired at or lies
+	/// in the future, the operation fails with [`Error::ClipboardOccupied`] and the contents of
+	/// the clipboard are left unchanged. That is unless this process already owned the selection,
+	/// in which case the contents are replaced without updating the timestamp.
+	///
+	/// This has no effect when the Wayland backend is in use.
+	fn time(self, time: u32) -> Self;
+
+	/// Places images as palette-based PNG files where that's possible without changing any
+	/// pixel, which is when they have at most 256 distinct colors.
+	///
+	/// This makes images with few colors, like screenshots of user interfaces, much smaller to
+	/// hold and transfer. Images with more colors are placed as RGBA PNG files as usual.
+	fn indexed_png(self) -> Self;
 }
 
 impl SetExtLinux for crate::Set<'_> {
@@ -239,6 +774,26 @@ impl SetExtLinux for crate::Set<'_> {
 		self.platform.selection = selection;
 		self
 	}
+
+	fn owner_name(mut self, name: &str) -> Self {
+		self.platform.owner_name = Some(name.to_owned());
+		self
+	}
+
+	fn mirror_primary(mut self) -> Self {
+		self.platform.mirror_primary = true;
+		self
+	}
+
+	fn time(mut self, time: u32) -> Self {
+		self.platform.time = Some(time);
+		self
+	}
+
+	fn indexed_png(mut self) -> Self {
+		self.platform.indexed_png = true;
+		self
+	}
 }
 
 pub(crate) struct Clear<'clipboard> {
@@ -251,14 +806,16 @@ impl<'clipboard> Clear<'clipboard> {
 	}
 
 	pub(crate) fn clear(self) -> Result<(), Error> {
-		self.clear_inner(LinuxClipboardKind::Clipboard)
+		let selection = self.clipboard.default_selection;
+		self.clear_inner(selection)
 	}
 
 	fn clear_inner(self, selection: LinuxClipboardKind) -> Result<(), Error> {
-		let mut set = Set::new(self.clipboard);
-		set.selection = selection;
-
-		set.text(Cow::Borrowed(""))
+		match &mut self.clipboard.backend {
+			Backend::X11(clipboard) => clipboard.clear(selection),
+			#[cfg(feature = "wayland-data-control")]
+			Backend::WlDataControl(clipboard) => clipboard.clear(selection),
+		}
 	}
 }
 
@@ -266,15 +823,21 @@ impl<'clipboard> Clear<'clipboard> {
 pub trait ClearExtLinux: private::Sealed {
 	/// Performs the "clear" operation on the selected clipboard.
 	///
+	/// Instead of placing empty data onto the selection, this gives up its ownership, so that
+	/// other applications see it as having no content at all.
+	///
 	/// ### Example
 	///
 	/// ```no_run
-	/// # use arboard::Clipboard;
+	/// # use arboard::{Clipboard, ClearExtLinux, LinuxClipboardKind};
+	/// # fn main() -> Result<(), arboard::Error> {
 	/// let mut clipboard = Clipboard::new()?;
 	///
 	/// clipboard
 	///     .clear_with()
 	///     .clipboard(LinuxClipboardKind::Secondary)?;
+	/// # Ok(())
+	/// # }
 	/// ```
 	///
 	/// If wayland support is enabled and available, attempting to use the Secondary clipboard will
@@ -287,3 +850,168 @@ impl ClearExtLinux for crate::Clear<'_> {
 		self.platform.clear_inner(selection)
 	}
 }
+
+#[cfg(all(test, feature = "image-data"))]
+mod tests {
+	use super::{decode_png, encode_as_indexed_png, encode_as_png, png_dpi, png_icc_profile};
+	use crate::{Error, ImageData};
+
+	#[test]
+	fn decode_png_color_types() {
+		use png::{BitDepth, ColorType};
+
+		fn encode(color: ColorType, depth: BitDepth, data: &[u8], palette: &[u8]) -> Vec<u8> {
+			let mut png = Vec::new();
+			let mut encoder = png::Encoder::new(&mut png, 2, 1);
+			encoder.set_color(color);
+			encoder.set_depth(depth);
+			if !palette.is_empty() {
+				encoder.set_palette(palette);
+				// Only the second palette entry is translucent.
+				encoder.set_trns(vec![255, 64]);
+			}
+			encoder.write_header().unwrap().write_image_data(data).unwrap();
+			png
+		}
+
+		/// The color type, bit depth, pixel data and palette of the image, followed by the RGBA
+		/// pixels it's expected to decode to.
+		type Case = (ColorType, BitDepth, &'static [u8], &'static [u8], [u8; 8]);
+
+		#[rustfmt::skip]
+		let cases: &[Case] = &[
+			(ColorType::Grayscale, BitDepth::Eight, &[0, 200], &[], [0, 0, 0, 255, 200, 200, 200, 255]),
+			// Two 1-bit pixels packed into a single, padded byte.
+			(ColorType::Grayscale, BitDepth::One, &[0b0100_0000], &[], [0, 0, 0, 255, 255, 255, 255, 255]),
+			(ColorType::GrayscaleAlpha, BitDepth::Eight, &[10, 20, 30, 40], &[], [10, 10, 10, 20, 30, 30, 30, 40]),
+			(ColorType::Indexed, BitDepth::Eight, &[1, 0], &[1, 2, 3, 4, 5, 6], [4, 5, 6, 64, 1, 2, 3, 255]),
+			(ColorType::Rgb, BitDepth::Eight, &[1, 2, 3, 4, 5, 6], &[], [1, 2, 3, 255, 4, 5, 6, 255]),
+			(ColorType::Rgba, BitDepth::Eight, &[1, 2, 3, 4, 5, 6, 7, 8], &[], [1, 2, 3, 4, 5, 6, 7, 8]),
+			// Big-endian 16-bit samples, of which only the most significant byte is kept.
+			(
+				ColorType::Rgb, BitDepth::Sixteen, &[255, 255, 128, 128, 0, 0, 0, 0, 1, 1, 255, 255], &[],
+				[255, 128, 0, 255, 0, 1, 255, 255],
+			),
+			(ColorType::Grayscale, BitDepth::Sixteen, &[0, 0, 255, 255], &[], [0, 0, 0, 255, 255, 255, 255, 255]),
+		];
+
+		for (color, depth, data, palette, expected) in cases {
+			let png = encode(*color, *depth, data, palette);
+			let (image, _) = decode_png(&png, u64::MAX)
+				.unwrap_or_else(|e| panic!("{:?} at {:?} failed to decode: {}", color, depth, e));
+			assert_eq!((image.width, image.height), (2, 1));
+			assert_eq!(image.bytes.as_ref(), expected, "{:?} at {:?}", color, depth);
+		}
+	}
+
+	#[test]
+	fn decode_png_size_limit() {
+		let mut png = Vec::new();
+		let mut encoder = png::Encoder::new(&mut png, 3, 2);
+		encoder.set_color(png::ColorType::Grayscale);
+		encoder.write_header().unwrap().write_image_data(&[0; 6]).unwrap();
+
+		assert!(matches!(decode_png(&png, 5), Err(Error::TooLarge)));
+		assert_eq!(decode_png(&png, 6).unwrap().0.bytes.len(), 6 * 4);
+	}
+
+	#[test]
+	fn encode_indexed_png() {
+		use png::{BitDepth, ColorType};
+
+		let format = |png: &[u8]| {
+			let reader = png::Decoder::new(png).read_info().unwrap();
+			(reader.info().color_type, reader.info().bit_depth)
+		};
+
+		// Three colors, one of them translucent, fit into two bits per pixel. Each row of three
+		// pixels is padded to a full byte.
+		#[rustfmt::skip]
+		let bytes = [
+			255, 0, 0, 255,  0, 255, 0, 128,  255, 0, 0, 255,
+			0, 0, 255, 255,  0, 255, 0, 128,  0, 0, 255, 255,
+		];
+		let image = ImageData { width: 3, height: 2, bytes: bytes.as_ref().into() };
+		let png = encode_as_png(&image, true).unwrap();
+		assert_eq!(format(&png), (ColorType::Indexed, BitDepth::Two));
+		assert_eq!(*decode_png(&png, u64::MAX).unwrap().0.bytes, bytes);
+		assert_eq!(format(&encode_as_png(&image, false).unwrap()).0, ColorType::Rgba);
+
+		// There's no room for a 257th color.
+		let bytes: Vec<u8> = (0..=256u32).flat_map(|i| [i as u8, (i >> 8) as u8, 0, 255]).collect();
+		let image = ImageData { width: 257, height: 1, bytes: bytes.into() };
+		assert_eq!(format(&encode_as_png(&image, true).unwrap()).0, ColorType::Rgba);
+
+		// Sizes that overflow never get a palette.
+		let image = ImageData { width: usize::MAX / 4 + 1, height: 1, bytes: Vec::new().into() };
+		assert!(matches!(encode_as_indexed_png(&image), Ok(None)));
+	}
+
+	#[test]
+	fn read_png_resolution() {
+		fn chunk(chunk_type: &[u8], data: &[u8]) -> Vec<u8> {
+			let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+			chunk.extend_from_slice(chunk_type);
+			chunk.extend_from_slice(data);
+			// The checksum isn't verified.
+			chunk.extend_from_slice(&[0; 4]);
+			chunk
+		}
+		let png = |chunks: &[Vec<u8>]| -> Vec<u8> {
+			let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+			png.extend(chunks.concat());
+			png
+		};
+
+		// 3780 pixels per meter are just about 96 DPI.
+		let mut phys = [3780u32.to_be_bytes(), 7560u32.to_be_bytes()].concat();
+		phys.push(1);
+		let (x, y) = png_dpi(&png(&[chunk(b"IHDR", &[0; 13]), chunk(b"pHYs", &phys)])).unwrap();
+		assert_eq!((x.round(), y.round()), (96.0, 192.0));
+
+		// Without a unit, only the aspect ratio is known.
+		*phys.last_mut().unwrap() = 0;
+		assert_eq!(png_dpi(&png(&[chunk(b"IHDR", &[0; 13]), chunk(b"pHYs", &phys)])), None);
+
+		assert_eq!(png_dpi(&png(&[chunk(b"IHDR", &[0; 13]), chunk(b"IDAT", &[])])), None);
+		assert_eq!(png_dpi(&png(&[])), None);
+		assert_eq!(png_dpi(b"\x89PNG"), None);
+	}
+
+	#[test]
+	fn read_png_icc_profile() {
+		fn encode(icc_profile: Option<&[u8]>) -> Vec<u8> {
+			let mut png = Vec::new();
+			let mut encoder = png::Encoder::new(&mut png, 1, 1);
+			encoder.set_color(png::ColorType::Grayscale);
+			let mut writer = encoder.write_header().unwrap();
+			if let Some(profile) = icc_profile {
+				// A profile name, the compression method and a zlib stream with a single stored
+				// block, so that the test doesn't need a compressor.
+				let mut data = b"test\0\0\x78\x01\x01".to_vec();
+				let len = profile.len() as u16;
+				data.extend_from_slice(&len.to_le_bytes());
+				data.extend_from_slice(&(!len).to_le_bytes());
+				data.extend_from_slice(profile);
+				let (a, b) = profile.iter().fold((1u32, 0u32), |(a, b), &byte| {
+					let a = (a + byte as u32) % 65521;
+					(a, (b + a) % 65521)
+				});
+				data.extend_from_slice(&((b << 16) | a).to_be_bytes());
+				writer.write_chunk(png::chunk::iCCP, &data).unwrap();
+			}
+			writer.write_image_data(&[0]).unwrap();
+			drop(writer);
+			png
+		}
+
+		let profile = b"not really a color profile";
+		let png = encode(Some(profile));
+		assert_eq!(png_icc_profile(&png).as_deref(), Some(&profile[..]));
+		let (_, metadata) = decode_png(&png, u64::MAX).unwrap();
+		assert_eq!(metadata.icc_profile.as_deref(), Some(&profile[..]));
+
+		assert_eq!(png_icc_profile(&encode(None)), None);
+		assert_eq!(png_icc_profile(b"\x89PNG"), None);
+	}
+}
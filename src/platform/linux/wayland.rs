@@ -18,6 +18,9 @@ use crate::common::ImageData;
 #[cfg(feature = "image-data")]
 const MIME_PNG: &str = "image/png";
 
+/// The types HTML is offered as, since applications differ in which of them they ask for.
+const MIME_HTML: &[&str] = &["text/html", "text/html;charset=utf-8"];
+
 pub(crate) struct Clipboard {}
 
 impl TryInto<copy::ClipboardType> for LinuxClipboardKind {
@@ -54,25 +57,51 @@ impl Clipboard {
 		Ok(Self {})
 	}
 
-	pub(crate) fn get_text(&mut self, selection: LinuxClipboardKind) -> Result<String, Error> {
-		use wl_clipboard_rs::paste::MimeType;
+	/// Returns [`Error::ClipboardNotSupported`] if `selection` can't be used on this compositor.
+	pub(crate) fn check_supported(&self, selection: LinuxClipboardKind) -> Result<(), Error> {
+		match selection {
+			LinuxClipboardKind::Clipboard => Ok(()),
+			LinuxClipboardKind::Primary => match is_primary_selection_supported() {
+				Ok(true) => Ok(()),
+				Ok(false) => Err(Error::ClipboardNotSupported),
+				Err(e) => Err(into_unknown(e)),
+			},
+			LinuxClipboardKind::Secondary => Err(Error::ClipboardNotSupported),
+		}
+	}
 
-		let result = get_contents(selection.try_into()?, Seat::Unspecified, MimeType::Text);
-		match result {
-			Ok((mut pipe, _)) => {
-				let mut contents = vec![];
-				pipe.read_to_end(&mut contents).map_err(into_unknown)?;
-				String::from_utf8(contents).map_err(|_| Error::ConversionFailure)
-			}
+	pub(crate) fn clear(&mut self, selection: LinuxClipboardKind) -> Result<(), Error> {
+		copy::clear(selection.try_into()?, copy::Seat::All).map_err(|e| match e {
+			CopyError::PrimarySelectionUnsupported => Error::ClipboardNotSupported,
+			other => into_unknown(other),
+		})
+	}
 
-			Err(PasteError::ClipboardEmpty) | Err(PasteError::NoMimeType) => {
-				Err(Error::ContentNotAvailable)
+	pub(crate) fn available_selections(&mut self) -> Result<Vec<LinuxClipboardKind>, Error> {
+		let mut available = Vec::new();
+		// The secondary selection doesn't exist on Wayland.
+		for selection in [LinuxClipboardKind::Clipboard, LinuxClipboardKind::Primary] {
+			match paste::get_mime_types(selection.try_into()?, Seat::Unspecified) {
+				Ok(types) if !types.is_empty() => available.push(selection),
+				Ok(_)
+				| Err(PasteError::ClipboardEmpty)
+				| Err(PasteError::PrimarySelectionUnsupported) => {}
+				Err(err) => return Err(Error::Unknown { description: format!("{}", err) }),
 			}
+		}
+		Ok(available)
+	}
 
-			Err(PasteError::PrimarySelectionUnsupported) => Err(Error::ClipboardNotSupported),
+	pub(crate) fn get_text(
+		&mut self,
+		selection: LinuxClipboardKind,
+		verify: bool,
+	) -> Result<String, Error> {
+		use wl_clipboard_rs::paste::MimeType;
 
-			Err(err) => Err(Error::Unknown { description: format!("{}", err) }),
-		}
+		let read = || read_contents(selection, MimeType::Text);
+		let contents = if verify { read_until_stable(read) } else { read() }?;
+		String::from_utf8(contents).map_err(|_| Error::ConversionFailure)
 	}
 
 	pub(crate) fn set_text(
@@ -99,23 +128,24 @@ impl Clipboard {
 		selection: LinuxClipboardKind,
 		wait: bool,
 	) -> Result<(), Error> {
-		let html_mime = MimeType::Specific(String::from("text/html"));
 		let mut opts = Options::new();
 		opts.foreground(wait);
 		opts.clipboard(selection.try_into()?);
-		let html_source = Source::Bytes(html.into_owned().into_bytes().into_boxed_slice());
-		match alt {
-			Some(alt_text) => {
-				let alt_source =
-					Source::Bytes(alt_text.into_owned().into_bytes().into_boxed_slice());
-				opts.copy_multi(vec![
-					MimeSource { source: alt_source, mime_type: MimeType::Text },
-					MimeSource { source: html_source, mime_type: html_mime },
-				])
-			}
-			None => opts.copy(html_source, html_mime),
+
+		let mut sources = Vec::with_capacity(MIME_HTML.len() + 1);
+		if let Some(alt_text) = alt {
+			let alt_source = Source::Bytes(alt_text.into_owned().into_bytes().into_boxed_slice());
+			sources.push(MimeSource { source: alt_source, mime_type: MimeType::Text });
 		}
-		.map_err(|e| match e {
+		let html = html.into_owned().into_bytes().into_boxed_slice();
+		for mime in MIME_HTML {
+			sources.push(MimeSource {
+				source: Source::Bytes(html.clone()),
+				mime_type: MimeType::Specific(String::from(*mime)),
+			});
+		}
+
+		opts.copy_multi(sources).map_err(|e| match e {
 			CopyError::PrimarySelectionUnsupported => Error::ClipboardNotSupported,
 			other => into_unknown(other),
 		})?;
@@ -123,38 +153,45 @@ impl Clipboard {
 	}
 
 	#[cfg(feature = "image-data")]
-	pub(crate) fn get_image(
+	pub(crate) fn get_png(
 		&mut self,
 		selection: LinuxClipboardKind,
-	) -> Result<ImageData<'static>, Error> {
-		use std::io::Cursor;
-		use wl_clipboard_rs::paste::MimeType;
+		verify: bool,
+	) -> Result<Vec<u8>, Error> {
+		self.get_raw(selection, MIME_PNG, verify)
+	}
 
-		let result =
-			get_contents(selection.try_into()?, Seat::Unspecified, MimeType::Specific(MIME_PNG));
-		match result {
-			Ok((mut pipe, _mime_type)) => {
-				let mut buffer = vec![];
-				pipe.read_to_end(&mut buffer).map_err(into_unknown)?;
-				let image = image::io::Reader::new(Cursor::new(buffer))
-					.with_guessed_format()
-					.map_err(|_| Error::ConversionFailure)?
-					.decode()
-					.map_err(|_| Error::ConversionFailure)?;
-				let image = image.into_rgba8();
-
-				Ok(ImageData {
-					width: image.width() as usize,
-					height: image.height() as usize,
-					bytes: image.into_raw().into(),
-				})
-			}
+	pub(crate) fn set_raw(
+		&mut self,
+		mime_type: &str,
+		bytes: Vec<u8>,
+		selection: LinuxClipboardKind,
+		wait: bool,
+	) -> Result<(), Error> {
+		let mut opts = Options::new();
+		opts.foreground(wait);
+		opts.clipboard(selection.try_into()?);
+		let source = Source::Bytes(bytes.into_boxed_slice());
+		opts.copy(source, MimeType::Specific(String::from(mime_type))).map_err(|e| match e {
+			CopyError::PrimarySelectionUnsupported => Error::ClipboardNotSupported,
+			other => into_unknown(other),
+		})?;
+		Ok(())
+	}
 
-			Err(PasteError::ClipboardEmpty) | Err(PasteError::NoMimeType) => {
-				Err(Error::ContentNotAvailable)
-			}
+	pub(crate) fn get_raw(
+		&mut self,
+		selection: LinuxClipboardKind,
+		mime_type: &str,
+		verify: bool,
+	) -> Result<Vec<u8>, Error> {
+		use wl_clipboard_rs::paste::MimeType;
 
-			Err(err) => Err(Error::Unknown { description: format!("{}", err) }),
+		let read = || read_contents(selection, MimeType::Specific(mime_type));
+		if verify {
+			read_until_stable(read)
+		} else {
+			read()
 		}
 	}
 
@@ -164,8 +201,9 @@ impl Clipboard {
 		image: ImageData,
 		selection: LinuxClipboardKind,
 		wait: bool,
+		indexed_png: bool,
 	) -> Result<(), Error> {
-		let image = encode_as_png(&image)?;
+		let image = encode_as_png(&image, indexed_png)?;
 		let mut opts = Options::new();
 		opts.foreground(wait);
 		opts.clipboard(selection.try_into()?);
@@ -174,3 +212,76 @@ impl Clipboard {
 		Ok(())
 	}
 }
+
+/// How many times the contents are read while waiting for two reads to agree.
+const MAX_READ_ATTEMPTS: usize = 3;
+
+/// Reads the whole contents of `selection` offered as `mime_type`.
+fn read_contents(
+	selection: LinuxClipboardKind,
+	mime_type: paste::MimeType<'_>,
+) -> Result<Vec<u8>, Error> {
+	match get_contents(selection.try_into()?, Seat::Unspecified, mime_type) {
+		Ok((mut pipe, _mime_type)) => {
+			let mut buffer = vec![];
+			pipe.read_to_end(&mut buffer).map_err(into_unknown)?;
+			Ok(buffer)
+		}
+
+		Err(PasteError::ClipboardEmpty) | Err(PasteError::NoMimeType) => {
+			Err(Error::ContentNotAvailable)
+		}
+
+		Err(PasteError::PrimarySelectionUnsupported) => Err(Error::ClipboardNotSupported),
+
+		Err(err) => Err(Error::Unknown { description: format!("{}", err) }),
+	}
+}
+
+/// Calls `read` until it returns the same data twice in a row.
+///
+/// When the source revokes or replaces its offer while sending it, the pipe just ends early,
+/// which looks exactly like the end of the data. Reading it again is the only way to notice, see
+/// `GetExtLinux::verify_wayland_reads`. If the reads keep disagreeing,
+/// [`Error::IncompleteTransfer`] is returned.
+fn read_until_stable(mut read: impl FnMut() -> Result<Vec<u8>, Error>) -> Result<Vec<u8>, Error> {
+	let mut previous = read()?;
+	let (mut shortest, mut longest) = (previous.len(), previous.len());
+	for _ in 1..MAX_READ_ATTEMPTS {
+		let contents = read()?;
+		if contents == previous {
+			return Ok(contents);
+		}
+		shortest = shortest.min(contents.len());
+		longest = longest.max(contents.len());
+		previous = contents;
+	}
+	Err(Error::IncompleteTransfer { received: shortest, expected: longest })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::read_until_stable;
+	use crate::Error;
+
+	#[test]
+	fn reread_replaced_offer() {
+		// The offer is replaced while the first read is underway, cutting it short.
+		let mut reads = vec![Ok(b"new".to_vec()), Ok(b"new".to_vec()), Ok(b"ol".to_vec())];
+		assert_eq!(read_until_stable(|| reads.pop().unwrap()).unwrap(), b"new");
+		assert!(reads.is_empty());
+
+		let mut reads = vec![Ok(b"abc".to_vec()), Ok(b"ab".to_vec()), Ok(b"a".to_vec())];
+		assert!(matches!(
+			read_until_stable(|| reads.pop().unwrap()),
+			Err(Error::IncompleteTransfer { received: 1, expected: 3 })
+		));
+
+		// The offer disappearing in between is reported as is.
+		let mut reads = vec![Err(Error::ContentNotAvailable), Ok(b"gone".to_vec())];
+		assert!(matches!(
+			read_until_stable(|| reads.pop().unwrap()),
+			Err(Error::ContentNotAvailable)
+		));
+	}
+}
@@ -17,27 +17,27 @@ use std::{
 	cell::RefCell,
 	collections::{hash_map::Entry, HashMap},
 	sync::{
-		atomic::{AtomicBool, Ordering},
+		atomic::{AtomicBool, AtomicU32, Ordering},
 		Arc,
 	},
 	thread::JoinHandle,
 	thread_local,
 	time::{Duration, Instant},
-	usize,
 };
 
 use log::{error, trace, warn};
 use parking_lot::{Condvar, Mutex, MutexGuard, RwLock};
 use x11rb::{
-	connection::Connection,
+	connection::{Connection, RequestConnection as _},
 	protocol::{
 		xproto::{
-			Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, Property,
-			PropertyNotifyEvent, SelectionNotifyEvent, SelectionRequestEvent, Time, WindowClass,
-			SELECTION_NOTIFY_EVENT,
+			Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, CreateWindowAux,
+			EventMask, PropMode, Property, PropertyNotifyEvent, SelectionNotifyEvent,
+			SelectionRequestEvent, Time, Timestamp, Window, WindowClass, SELECTION_NOTIFY_EVENT,
 		},
 		Event,
 	},
+	reexports::x11rb_protocol::parse_display::{parse_display, ParsedDisplay},
 	rust_connection::RustConnection,
 	wrapper::ConnectionExt as _,
 	COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, NONE,
@@ -52,7 +52,8 @@ use crate::{common::ScopeGuard, Error};
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
-static CLIPBOARD: Mutex<Option<GlobalClipboard>> = parking_lot::const_mutex(None);
+/// The clipboards that are in use, one for each display connected to.
+static CLIPBOARDS: Mutex<Vec<GlobalClipboard>> = parking_lot::const_mutex(Vec::new());
 
 x11rb::atom_manager! {
 	pub Atoms: AtomCookies {
@@ -63,7 +64,11 @@ x11rb::atom_manager! {
 		CLIPBOARD_MANAGER,
 		SAVE_TARGETS,
 		TARGETS,
+		TIMESTAMP,
+		MULTIPLE,
 		ATOM,
+		ATOM_PAIR,
+		INTEGER,
 		INCR,
 
 		UTF8_STRING,
@@ -81,6 +86,8 @@ x11rb::atom_manager! {
 
 		PNG_MIME: b"image/png",
 
+		NET_WM_NAME: b"_NET_WM_NAME",
+
 		// This is just some random name for the property on our window, into which
 		// the clipboard owner writes the data we requested.
 		ARBOARD_CLIPBOARD,
@@ -95,17 +102,36 @@ thread_local! {
 // `SelectionNotify`. Multiple seconds long.
 const LONG_TIMEOUT_DUR: Duration = Duration::from_millis(4000);
 const SHORT_TIMEOUT_DUR: Duration = Duration::from_millis(10);
+// Owners sending large data in `INCR` chunks may need a moment to produce each of them.
+const INCR_CHUNK_TIMEOUT_DUR: Duration = Duration::from_millis(500);
+
+/// The largest chunk of data sent at once when serving large data with `INCR`, so that even
+/// several megabytes are split into a few chunks without any of them holding up the server.
+const MAX_INCR_CHUNK_LEN: usize = 1024 * 1024;
+/// The size of a `ChangeProperty` request without its data.
+const CHANGE_PROPERTY_HEADER_LEN: usize = 24;
 
 #[derive(Debug, PartialEq, Eq)]
 enum ManagerHandoverState {
 	Idle,
-	InProgress,
+	/// The clipboard manager was asked to save the contents. It's done once it has both
+	/// `written` them, meaning requested them from us, and `notified` us that it finished.
+	InProgress {
+		written: bool,
+		notified: bool,
+	},
 	Finished,
 }
 
 struct GlobalClipboard {
 	inner: Arc<Inner>,
 
+	/// The display `inner` is connected to, as parsed by x11rb.
+	display: Option<ParsedDisplay>,
+
+	/// Whether other instances for the same display use `inner` as well.
+	shared: bool,
+
 	/// Join handle to the thread which serves selection requests.
 	server_handle: JoinHandle<()>,
 }
@@ -116,9 +142,15 @@ struct XContext {
 }
 
 struct Inner {
+	/// The name of the display connected to, or `None` for the one named by `$DISPLAY`.
+	display: Option<String>,
+
 	/// The context for the thread which serves clipboard read
 	/// requests coming to us.
 	server: XContext,
+	/// A connection of its own that is only used to find out the current server time, since the
+	/// events of `server` are all consumed by the serving thread.
+	timestamp: Mutex<XContext>,
 	atoms: Atoms,
 
 	clipboard: Selection,
@@ -132,10 +164,10 @@ struct Inner {
 }
 
 impl XContext {
-	fn new() -> Result<Self> {
+	fn new(display: Option<&str>) -> Result<Self> {
 		// create a new connection to an X11 server
 		let (conn, screen_num): (RustConnection, _) =
-			RustConnection::connect(None).map_err(into_unknown)?;
+			RustConnection::connect(display).map_err(into_unknown)?;
 		let screen = conn
 			.setup()
 			.roots
@@ -181,6 +213,28 @@ struct Selection {
 	///
 	/// This is associated with `Self::mutex`.
 	data_changed: Condvar,
+	/// The server time at which the selection was last acquired, which is reported to `TIMESTAMP`
+	/// requests.
+	acquired_at: AtomicU32,
+	/// Whether an image set on this selection was dropped because it couldn't be encoded, which
+	/// is reported to reads of images from it until something else is set.
+	#[cfg(feature = "image-data")]
+	image_encode_failed: AtomicBool,
+}
+
+/// Data that is being sent to a requestor in chunks, using `INCR`.
+///
+/// See: https://tronche.com/gui/x/icccm/sec-2.html#s-2.7.2
+struct IncrTransfer {
+	requestor: Window,
+	property: Atom,
+	target: Atom,
+	data: Vec<u8>,
+	/// How many bytes of `data` were sent already.
+	sent: usize,
+	/// When the requestor last took a chunk, so that transfers to requestors that went away can
+	/// be dropped.
+	last_activity: Instant,
 }
 
 #[derive(Debug, Clone)]
@@ -189,22 +243,50 @@ struct ClipboardData {
 
 	/// The atom representing the format in which the data is encoded.
 	format: Atom,
+
+	/// An image that still has to be encoded into `bytes`, see [`Inner::encode_pending_images`].
+	#[cfg(feature = "image-data")]
+	unencoded_image: Option<Arc<ImageData<'static>>>,
+	/// Whether `unencoded_image` is to be encoded with a palette where possible.
+	#[cfg(feature = "image-data")]
+	indexed_png: bool,
+}
+
+impl ClipboardData {
+	fn new(bytes: Vec<u8>, format: Atom) -> Self {
+		Self {
+			bytes,
+			format,
+			#[cfg(feature = "image-data")]
+			unencoded_image: None,
+			#[cfg(feature = "image-data")]
+			indexed_png: false,
+		}
+	}
 }
 
 enum ReadSelNotifyResult {
-	GotData(Vec<u8>),
-	IncrStarted,
+	GotData(ClipboardData),
+	/// The data is going to be sent in chunks, making up at least `min_len` bytes.
+	IncrStarted {
+		min_len: usize,
+	},
+	/// The owner announced the data before writing it to the property, so it has to be read
+	/// once the property changes.
+	PropertyNotReady,
 	EventNotRecognized,
 }
 
 impl Inner {
-	fn new() -> Result<Self> {
-		let server = XContext::new()?;
+	fn new(display: Option<&str>) -> Result<Self> {
+		let server = XContext::new(display)?;
 		let atoms =
 			Atoms::new(&server.conn).map_err(into_unknown)?.reply().map_err(into_unknown)?;
 
 		Ok(Self {
+			display: display.map(String::from),
 			server,
+			timestamp: Mutex::new(XContext::new(display)?),
 			atoms,
 			clipboard: Selection::default(),
 			primary: Selection::default(),
@@ -215,11 +297,15 @@ impl Inner {
 		})
 	}
 
+	/// Takes ownership of `selection` and starts serving `data` from it.
+	///
+	/// Ownership is acquired at `time` if given, or else at the current server time.
 	fn write(
 		&self,
 		data: Vec<ClipboardData>,
 		selection: LinuxClipboardKind,
 		wait: bool,
+		time: Option<Timestamp>,
 	) -> Result<()> {
 		if self.serve_stopped.load(Ordering::Relaxed) {
 			return Err(Error::Unknown {
@@ -229,19 +315,43 @@ impl Inner {
 
 		let server_win = self.server.win_id;
 
+		// ICCCM section 2.1 asks for an actual timestamp instead of `CurrentTime`, which is also
+		// what `TIMESTAMP` requests are answered with.
+		let time = match time {
+			Some(time) => time,
+			None => self.server_time()?,
+		};
+
+		// The server ignores a time older than the selection's last change, which it only tells
+		// apart from acquiring the selection when it wasn't ours before.
+		let was_owner = self.is_owner(selection)?;
+
 		// ICCCM version 2, section 2.6.1.3 states that we should re-assert ownership whenever data
 		// changes.
 		self.server
 			.conn
-			.set_selection_owner(server_win, self.atom_of(selection), Time::CURRENT_TIME)
+			.set_selection_owner(server_win, self.atom_of(selection), time)
 			.map_err(|_| Error::ClipboardOccupied)?;
 
 		self.server.conn.flush().map_err(into_unknown)?;
 
+		// The server silently ignores the request if the given time is older than the one the
+		// selection was last acquired at, or lies in the future. Another client may also have
+		// acquired the selection right after us, in which case the data would never be served.
+		if !self.is_owner(selection)? {
+			return Err(Error::ClipboardOccupied);
+		}
+
 		// Just setting the data, and the `serve_requests` will take care of the rest.
 		let selection = self.selection_of(selection);
+		// While owning the selection, the server only takes the time over if it's not older.
+		if !was_owner || time >= selection.acquired_at.load(Ordering::Relaxed) {
+			selection.acquired_at.store(time, Ordering::Relaxed);
+		}
 		let mut data_guard = selection.data.write();
 		*data_guard = Some(data);
+		#[cfg(feature = "image-data")]
+		selection.image_encode_failed.store(false, Ordering::Relaxed);
 
 		// Lock the mutex to both ensure that no wakers of `data_changed` can wake us between
 		// dropping the `data_guard` and calling `wait[_for]` and that we don't we wake other
@@ -262,12 +372,64 @@ impl Inner {
 		Ok(())
 	}
 
+	/// Returns the current server time.
+	///
+	/// The X server only reports it with events, so a property is changed to get one.
+	fn server_time(&self) -> Result<Timestamp> {
+		let context = self.timestamp.lock();
+		// Appending nothing leaves the property as it is, but still generates an event.
+		context
+			.conn
+			.change_property8(
+				PropMode::APPEND,
+				context.win_id,
+				self.atoms.ARBOARD_CLIPBOARD,
+				AtomEnum::STRING,
+				&[],
+			)
+			.map_err(into_unknown)?;
+		context.conn.flush().map_err(into_unknown)?;
+
+		let timeout_end = Instant::now() + LONG_TIMEOUT_DUR;
+		while Instant::now() < timeout_end {
+			match context.conn.poll_for_event().map_err(into_unknown)? {
+				Some(Event::PropertyNotify(event)) if event.window == context.win_id => {
+					return Ok(event.time);
+				}
+				Some(_) => {}
+				None => std::thread::sleep(Duration::from_millis(1)),
+			}
+		}
+		Err(Error::Unknown { description: "The X server didn't report the current time.".into() })
+	}
+
 	/// `formats` must be a slice of atoms, where each atom represents a target format.
 	/// The first format from `formats`, which the clipboard owner supports will be the
 	/// format of the return value.
-	fn read(&self, formats: &[Atom], selection: LinuxClipboardKind) -> Result<ClipboardData> {
+	fn read(
+		&self,
+		formats: &[Atom],
+		selection: LinuxClipboardKind,
+		cancel: Option<&AtomicBool>,
+	) -> Result<ClipboardData> {
 		// if we are the current owner, we can get the current clipboard ourselves
 		if self.is_owner(selection)? {
+			// The targets of the protocol itself are answered the same way as for other
+			// clients, see `convert_target`.
+			for &format in formats {
+				if format == self.atoms.TARGETS {
+					let targets = self.targets_of(selection);
+					let bytes = targets.iter().flat_map(|target| target.to_ne_bytes()).collect();
+					return Ok(ClipboardData::new(bytes, format));
+				}
+				if format == self.atoms.TIMESTAMP {
+					let time = self.selection_of(selection).acquired_at.load(Ordering::Relaxed);
+					return Ok(ClipboardData::new(time.to_ne_bytes().to_vec(), format));
+				}
+			}
+
+			#[cfg(feature = "image-data")]
+			self.encode_pending_images(selection);
 			let data = self.selection_of(selection).data.read();
 			if let Some(data_list) = &*data {
 				for data in data_list {
@@ -275,21 +437,33 @@ impl Inner {
 						if *format == data.format {
 							return Ok(data.clone());
 						}
+						if data.format == self.atoms.UTF8_STRING
+							&& self.utf8_aliases().contains(format)
+						{
+							return Ok(ClipboardData::new(data.bytes.clone(), *format));
+						}
 					}
 				}
 			}
+			// The image was set, so not finding it is due to it failing to encode.
+			#[cfg(feature = "image-data")]
+			if formats.contains(&self.atoms.PNG_MIME)
+				&& self.selection_of(selection).image_encode_failed.load(Ordering::Relaxed)
+			{
+				return Err(Error::ConversionFailure);
+			}
 			return Err(Error::ContentNotAvailable);
 		}
 		// if let Some(data) = self.data.read().clone() {
 		//     return Ok(data)
 		// }
-		let reader = XContext::new()?;
+		let reader = XContext::new(self.display.as_deref())?;
 
 		trace!("Trying to get the clipboard data.");
 		for format in formats {
-			match self.read_single(&reader, selection, *format) {
-				Ok(bytes) => {
-					return Ok(ClipboardData { bytes, format: *format });
+			match self.read_single(&reader, selection, *format, cancel) {
+				Ok(data) => {
+					return Ok(data);
 				}
 				Err(Error::ContentNotAvailable) => {
 					continue;
@@ -305,7 +479,8 @@ impl Inner {
 		reader: &XContext,
 		selection: LinuxClipboardKind,
 		target_format: Atom,
-	) -> Result<Vec<u8>> {
+		cancel: Option<&AtomicBool>,
+	) -> Result<ClipboardData> {
 		// Delete the property so that we can detect (using property notify)
 		// when the selection owner receives our request.
 		reader
@@ -330,10 +505,22 @@ impl Inner {
 
 		let mut incr_data: Vec<u8> = Vec::new();
 		let mut using_incr = false;
+		let mut incr_expected = 0;
+		let mut awaiting_property = false;
 
 		let mut timeout_end = Instant::now() + LONG_TIMEOUT_DUR;
 
 		while Instant::now() < timeout_end {
+			if cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+				// Don't leave any data the owner may have already sent behind.
+				reader
+					.conn
+					.delete_property(reader.win_id, self.atoms.ARBOARD_CLIPBOARD)
+					.map_err(into_unknown)?;
+				reader.conn.flush().map_err(into_unknown)?;
+				return Err(Error::Cancelled);
+			}
+
 			let event = reader.conn.poll_for_event().map_err(into_unknown)?;
 			let event = match event {
 				Some(e) => e,
@@ -342,27 +529,34 @@ impl Inner {
 					continue;
 				}
 			};
-			match event {
+			let result = match event {
 				// The first response after requesting a selection.
 				Event::SelectionNotify(event) => {
 					trace!("Read SelectionNotify");
-					let result = self.handle_read_selection_notify(
+					self.handle_read_selection_notify(
 						reader,
 						target_format,
 						&mut using_incr,
 						&mut incr_data,
 						event,
-					)?;
-					match result {
-						ReadSelNotifyResult::GotData(data) => return Ok(data),
-						ReadSelNotifyResult::IncrStarted => {
-							// This means we received an indication that an the
-							// data is going to be sent INCRementally. Let's
-							// reset our timeout.
-							timeout_end += SHORT_TIMEOUT_DUR;
-						}
-						ReadSelNotifyResult::EventNotRecognized => (),
+					)?
+				}
+				// Some owners send SelectionNotify before their write to the property has been
+				// processed, in which case the property is read again as soon as it's written.
+				Event::PropertyNotify(event) if awaiting_property => {
+					if event.atom != self.atoms.ARBOARD_CLIPBOARD
+						|| event.state != Property::NEW_VALUE
+					{
+						continue;
 					}
+					trace!("The property was written after SelectionNotify, reading it again");
+					self.read_property(
+						reader,
+						event.window,
+						target_format,
+						&mut using_incr,
+						&mut incr_data,
+					)?
 				}
 				// If the previous SelectionNotify event specified that the data
 				// will be sent in INCR segments, each segment is transferred in
@@ -377,13 +571,45 @@ impl Inner {
 						event,
 					)?;
 					if result {
-						return Ok(incr_data);
+						// The owner announced a lower bound of the size, so anything less than
+						// that means some chunks were lost along the way.
+						if incr_data.len() < incr_expected {
+							return Err(Error::IncompleteTransfer {
+								received: incr_data.len(),
+								expected: incr_expected,
+							});
+						}
+						return Ok(ClipboardData::new(incr_data, target_format));
 					}
+					continue;
+				}
+				_ => {
+					log::trace!("An unexpected event arrived while reading the clipboard.");
+					continue;
+				}
+			};
+			match result {
+				ReadSelNotifyResult::GotData(data) => return Ok(data),
+				ReadSelNotifyResult::IncrStarted { min_len } => {
+					// This means we received an indication that an the
+					// data is going to be sent INCRementally. Let's
+					// reset our timeout.
+					timeout_end += SHORT_TIMEOUT_DUR;
+					incr_expected = min_len;
+					awaiting_property = false;
 				}
-				_ => log::trace!("An unexpected event arrived while reading the clipboard."),
+				ReadSelNotifyResult::PropertyNotReady => awaiting_property = true,
+				ReadSelNotifyResult::EventNotRecognized => (),
 			}
 		}
 		log::info!("Time-out hit while reading the clipboard.");
+		if using_incr {
+			// Returning what has arrived so far would hand out truncated data.
+			return Err(Error::IncompleteTransfer {
+				received: incr_data.len(),
+				expected: incr_expected,
+			});
+		}
 		Err(Error::ContentNotAvailable)
 	}
 
@@ -412,6 +638,54 @@ impl Inner {
 		}
 	}
 
+	/// Encodes any images that were set on `selection`, so that their `bytes` can be served.
+	///
+	/// Images that fail to encode are no longer offered, and reading an image from the selection
+	/// returns [`Error::ConversionFailure`] instead.
+	#[cfg(feature = "image-data")]
+	fn encode_pending_images(&self, selection: LinuxClipboardKind) {
+		let selection = self.selection_of(selection);
+		let pending: Vec<(Arc<ImageData<'static>>, bool)> = match &*selection.data.read() {
+			Some(data_list) => data_list
+				.iter()
+				.filter_map(|data| {
+					Some((Arc::clone(data.unencoded_image.as_ref()?), data.indexed_png))
+				})
+				.collect(),
+			None => return,
+		};
+
+		// Encoding can take a while, so it's done without blocking everything else that uses the
+		// data in the meantime.
+		for (image, indexed_png) in pending {
+			let result = encode_as_png(&image, indexed_png);
+
+			let mut data = selection.data.write();
+			// The data may have been replaced, or the image encoded by another thread, since.
+			let data_list = match &mut *data {
+				Some(data_list) => data_list,
+				None => continue,
+			};
+			let index = match data_list.iter().position(|data| {
+				data.unencoded_image.as_ref().is_some_and(|pending| Arc::ptr_eq(pending, &image))
+			}) {
+				Some(index) => index,
+				None => continue,
+			};
+			match result {
+				Ok(encoded) => {
+					data_list[index].bytes = encoded;
+					data_list[index].unencoded_image = None;
+				}
+				Err(e) => {
+					error!("Failed to encode the image set on the clipboard: {}", e);
+					selection.image_encode_failed.store(true, Ordering::Relaxed);
+					data_list.remove(index);
+				}
+			}
+		}
+	}
+
 	fn is_owner(&self, selection: LinuxClipboardKind) -> Result<bool> {
 		let current = self
 			.server
@@ -462,12 +736,17 @@ impl Inner {
 		incr_data: &mut Vec<u8>,
 		event: SelectionNotifyEvent,
 	) -> Result<ReadSelNotifyResult> {
+		// According to: https://tronche.com/gui/x/icccm/sec-2.html#s-2.4
+		// the target must be set to the same as what we requested. A mismatching target can
+		// only be a late answer to an earlier request which timed out, so it must not fail
+		// the current one.
+		if event.target != target_format {
+			log::info!("Received a SelectionNotify for a target other than the requested one. Ignoring it.");
+			return Ok(ReadSelNotifyResult::EventNotRecognized);
+		}
 		// The property being set to NONE means that the `convert_selection`
 		// failed.
-
-		// According to: https://tronche.com/gui/x/icccm/sec-2.html#s-2.4
-		// the target must be set to the same as what we requested.
-		if event.property == NONE || event.target != target_format {
+		if event.property == NONE {
 			return Err(Error::ContentNotAvailable);
 		}
 		if self.kind_of(event.selection).is_none() {
@@ -478,44 +757,51 @@ impl Inner {
 			log::warn!("Received a SelectionNotify while already expecting INCR segments.");
 			return Ok(ReadSelNotifyResult::EventNotRecognized);
 		}
-		// request the selection
-		let mut reply = reader
+		self.read_property(reader, event.requestor, target_format, using_incr, incr_data)
+	}
+
+	/// Reads the data the selection owner stored in the `ARBOARD_CLIPBOARD` property of `window`.
+	fn read_property(
+		&self,
+		reader: &XContext,
+		window: Window,
+		target_format: Atom,
+		using_incr: &mut bool,
+		incr_data: &mut Vec<u8>,
+	) -> Result<ReadSelNotifyResult> {
+		let property = self.atoms.ARBOARD_CLIPBOARD;
+		// Any type is asked for, as the property is only deleted when the type matches.
+		let reply = reader
 			.conn
-			.get_property(true, event.requestor, event.property, event.target, 0, u32::MAX / 4)
+			.get_property(true, window, property, AtomEnum::ANY, 0, u32::MAX / 4)
 			.map_err(into_unknown)?
 			.reply()
 			.map_err(into_unknown)?;
 
-		// trace!("Property.type: {:?}", self.atom_name(reply.type_));
-
-		// we found something
-		if reply.type_ == target_format {
-			Ok(ReadSelNotifyResult::GotData(reply.value))
-		} else if reply.type_ == self.atoms.INCR {
-			// Note that we call the get_property again because we are
-			// indicating that we are ready to receive the data by deleting the
-			// property, however deleting only works if the type matches the
-			// property type. But the type didn't match in the previous call.
-			reply = reader
-				.conn
-				.get_property(
-					true,
-					event.requestor,
-					event.property,
-					self.atoms.INCR,
-					0,
-					u32::MAX / 4,
-				)
-				.map_err(into_unknown)?
-				.reply()
-				.map_err(into_unknown)?;
+		// A property that doesn't exist (yet) has no type.
+		if reply.type_ == NONE {
+			return Ok(ReadSelNotifyResult::PropertyNotReady);
+		}
+
+		if reply.type_ == self.atoms.INCR {
+			// Deleting the property above indicated that we are ready to receive the data.
 			log::trace!("Receiving INCR segments");
 			*using_incr = true;
-			if reply.value_len == 4 {
-				let min_data_len = reply.value32().and_then(|mut vals| vals.next()).unwrap_or(0);
-				incr_data.reserve(min_data_len as usize);
-			}
-			Ok(ReadSelNotifyResult::IncrStarted)
+			let min_len = reply.value32().and_then(|mut vals| vals.next()).unwrap_or(0) as usize;
+			incr_data.reserve(min_len);
+			return Ok(ReadSelNotifyResult::IncrStarted { min_len });
+		}
+
+		// Owners may answer a request for `TEXT` with any of the text encodings they support.
+		// See: https://tronche.com/gui/x/icccm/sec-2.html#s-2.6.2
+		let is_text_answer = target_format == self.atoms.TEXT
+			&& (reply.type_ == self.atoms.STRING || reply.type_ == self.atoms.UTF8_STRING);
+		// Other text has to be in the encoding that was asked for, but the remaining targets may
+		// have a type of their own, like `ATOM` for `TARGETS`.
+		let is_other_answer = !self.is_text_target(target_format);
+
+		if reply.type_ == target_format || is_text_answer || is_other_answer {
+			Ok(ReadSelNotifyResult::GotData(ClipboardData::new(reply.value, reply.type_)))
 		} else {
 			// this should never happen, we have sent a request only for supported types
 			Err(Error::Unknown {
@@ -557,81 +843,317 @@ impl Inner {
 		incr_data.extend(reply.value);
 
 		// Let's reset our timeout, since we received a valid chunk.
-		*timeout_end = Instant::now() + SHORT_TIMEOUT_DUR;
+		*timeout_end = Instant::now() + INCR_CHUNK_TIMEOUT_DUR;
 
 		// Not yet complete
 		Ok(false)
 	}
 
-	fn handle_selection_request(&self, event: SelectionRequestEvent) -> Result<()> {
-		let selection = match self.kind_of(event.selection) {
-			Some(kind) => kind,
-			None => {
-				warn!("Received a selection request to a selection other than the CLIPBOARD, PRIMARY or SECONDARY. This is unexpected.");
-				return Ok(());
-			}
+	/// The other targets that text stored as `UTF8_STRING` is offered as, since some applications
+	/// only ask for one of these.
+	fn is_text_target(&self, atom: Atom) -> bool {
+		atom == self.atoms.UTF8_STRING
+			|| atom == self.atoms.STRING
+			|| atom == self.atoms.TEXT
+			|| atom == self.atoms.TEXT_MIME_UNKNOWN
+			|| self.utf8_aliases().contains(&atom)
+	}
+
+	fn utf8_aliases(&self) -> [Atom; 3] {
+		[self.atoms.UTF8_MIME_0, self.atoms.UTF8_MIME_1, self.atoms.TEXT_MIME_UNKNOWN]
+	}
+
+	/// The size of the chunks data is sent in with `INCR`, and above which it's sent that way.
+	fn incr_chunk_len(&self) -> usize {
+		let max_request_len = self.server.conn.maximum_request_bytes();
+		max_request_len.saturating_sub(CHANGE_PROPERTY_HEADER_LEN).min(MAX_INCR_CHUNK_LEN)
+	}
+
+	/// Announces to the requestor that `data` will be sent with `INCR`. Each chunk is written
+	/// once the requestor deleted the property holding the previous one, see
+	/// [`Self::send_incr_chunk`].
+	fn start_incr_transfer(
+		&self,
+		requestor: Window,
+		target: Atom,
+		property: Atom,
+		data: Vec<u8>,
+		transfers: &mut Vec<IncrTransfer>,
+	) -> Result<()> {
+		trace!("Sending {} bytes with INCR", data.len());
+		// The requestor's deletions of the property have to be seen to know when to continue.
+		self.server
+			.conn
+			.change_window_attributes(
+				requestor,
+				&ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+			)
+			.map_err(into_unknown)?;
+		// The value is a lower bound of the size, so it's fine for it to be capped.
+		let len = data.len().min(u32::MAX as usize) as u32;
+		self.server
+			.conn
+			.change_property32(PropMode::REPLACE, requestor, property, self.atoms.INCR, &[len])
+			.map_err(into_unknown)?;
+
+		transfers.retain(|t| t.requestor != requestor || t.property != property);
+		transfers.push(IncrTransfer {
+			requestor,
+			property,
+			target,
+			data,
+			sent: 0,
+			last_activity: Instant::now(),
+		});
+		Ok(())
+	}
+
+	/// Writes the next chunk of the `INCR` transfer whose property was just deleted, if any.
+	fn send_incr_chunk(
+		&self,
+		event: PropertyNotifyEvent,
+		transfers: &mut Vec<IncrTransfer>,
+	) -> Result<()> {
+		let index = match transfers
+			.iter()
+			.position(|t| t.requestor == event.window && t.property == event.atom)
+		{
+			Some(index) => index,
+			None => return Ok(()),
 		};
+		let transfer = &mut transfers[index];
+		let end = transfer.data.len().min(transfer.sent + self.incr_chunk_len());
+		let chunk = &transfer.data[transfer.sent..end];
+		self.server
+			.conn
+			.change_property8(
+				PropMode::REPLACE,
+				transfer.requestor,
+				transfer.property,
+				transfer.target,
+				chunk,
+			)
+			.map_err(into_unknown)?;
 
-		let success;
-		// we are asked for a list of supported conversion targets
-		if event.target == self.atoms.TARGETS {
-			trace!("Handling TARGETS, dst property is {}", self.atom_name_dbg(event.property));
-			let mut targets = Vec::with_capacity(10);
-			targets.push(self.atoms.TARGETS);
-			targets.push(self.atoms.SAVE_TARGETS);
-			let data = self.selection_of(selection).data.read();
-			if let Some(data_list) = &*data {
-				for data in data_list {
-					targets.push(data.format);
-					if data.format == self.atoms.UTF8_STRING {
-						// When we are storing a UTF8 string,
-						// add all equivalent formats to the supported targets
-						targets.push(self.atoms.UTF8_MIME_0);
-						targets.push(self.atoms.UTF8_MIME_1);
-					}
+		if chunk.is_empty() {
+			// The empty chunk that was just written marks the end of the transfer.
+			let requestor = transfers.swap_remove(index).requestor;
+			self.unwatch_requestor(requestor, transfers)?;
+		} else {
+			transfer.sent = end;
+			transfer.last_activity = Instant::now();
+		}
+		self.server.conn.flush().map_err(into_unknown)
+	}
+
+	/// Drops the `INCR` transfers whose requestor hasn't taken a chunk in a long time, which is
+	/// most likely because it went away.
+	fn prune_incr_transfers(&self, transfers: &mut Vec<IncrTransfer>) -> Result<()> {
+		let now = Instant::now();
+		let is_stale = |t: &IncrTransfer| now.duration_since(t.last_activity) >= LONG_TIMEOUT_DUR;
+		if !transfers.iter().any(is_stale) {
+			return Ok(());
+		}
+
+		let (stale, active) = transfers.drain(..).partition::<Vec<_>, _>(is_stale);
+		*transfers = active;
+		for transfer in stale {
+			trace!("Dropping a stale INCR transfer to {}", transfer.requestor);
+			self.unwatch_requestor(transfer.requestor, transfers)?;
+		}
+		self.server.conn.flush().map_err(into_unknown)
+	}
+
+	/// Stops listening to the property changes of `requestor`, unless data is still being sent
+	/// to it with one of `transfers`.
+	fn unwatch_requestor(&self, requestor: Window, transfers: &[IncrTransfer]) -> Result<()> {
+		if transfers.iter().any(|t| t.requestor == requestor) {
+			return Ok(());
+		}
+		self.server
+			.conn
+			.change_window_attributes(
+				requestor,
+				&ChangeWindowAttributesAux::new().event_mask(EventMask::NO_EVENT),
+			)
+			.map_err(into_unknown)?;
+		Ok(())
+	}
+
+	/// Returns the targets the selection's contents can be converted to, for the `TARGETS` target.
+	fn targets_of(&self, selection: LinuxClipboardKind) -> Vec<Atom> {
+		let mut targets = Vec::with_capacity(10);
+		targets.push(self.atoms.TARGETS);
+		targets.push(self.atoms.SAVE_TARGETS);
+		targets.push(self.atoms.TIMESTAMP);
+		targets.push(self.atoms.MULTIPLE);
+		let data = self.selection_of(selection).data.read();
+		if let Some(data_list) = &*data {
+			for data in data_list {
+				targets.push(data.format);
+				if data.format == self.atoms.UTF8_STRING {
+					// When we are storing a UTF8 string,
+					// add all equivalent formats to the supported targets
+					targets.extend_from_slice(&self.utf8_aliases());
 				}
 			}
+		}
+		targets
+	}
+
+	/// Writes the conversion of the selection to `target` into `property` on the requestor's
+	/// window, and returns whether the selection could be converted.
+	fn convert_target(
+		&self,
+		selection: LinuxClipboardKind,
+		requestor: Window,
+		target: Atom,
+		property: Atom,
+		transfers: &mut Vec<IncrTransfer>,
+	) -> Result<bool> {
+		// we are asked for a list of supported conversion targets
+		if target == self.atoms.TARGETS {
+			trace!("Handling TARGETS, dst property is {}", self.atom_name_dbg(property));
+			let targets = self.targets_of(selection);
 			self.server
 				.conn
 				.change_property32(
 					PropMode::REPLACE,
-					event.requestor,
-					event.property,
+					requestor,
+					property,
 					// TODO: change to `AtomEnum::ATOM`
 					self.atoms.ATOM,
 					&targets,
 				)
 				.map_err(into_unknown)?;
-			self.server.conn.flush().map_err(into_unknown)?;
-			success = true;
-		} else {
-			trace!("Handling request for (probably) the clipboard contents.");
-			let data = self.selection_of(selection).data.read();
-			if let Some(data_list) = &*data {
-				success = match data_list.iter().find(|d| d.format == event.target) {
-					Some(data) => {
-						self.server
-							.conn
-							.change_property8(
-								PropMode::REPLACE,
-								event.requestor,
-								event.property,
-								event.target,
-								&data.bytes,
-							)
-							.map_err(into_unknown)?;
-						self.server.conn.flush().map_err(into_unknown)?;
-						true
-					}
-					None => false,
-				};
-			} else {
-				// This must mean that we lost ownership of the data
-				// since the other side requested the selection.
-				// Let's respond with the property set to none.
-				success = false;
+			return Ok(true);
+		}
+
+		if target == self.atoms.TIMESTAMP {
+			let time = self.selection_of(selection).acquired_at.load(Ordering::Relaxed);
+			self.server
+				.conn
+				.change_property32(
+					PropMode::REPLACE,
+					requestor,
+					property,
+					self.atoms.INTEGER,
+					&[time],
+				)
+				.map_err(into_unknown)?;
+			return Ok(true);
+		}
+
+		trace!("Handling request for (probably) the clipboard contents.");
+		#[cfg(feature = "image-data")]
+		self.encode_pending_images(selection);
+		let data = self.selection_of(selection).data.read();
+		// Aliases are served from the UTF-8 text, but keep the type that was asked for.
+		let format =
+			if self.utf8_aliases().contains(&target) { self.atoms.UTF8_STRING } else { target };
+		let data_list = match &*data {
+			Some(data_list) => data_list,
+			// This must mean that we lost ownership of the data
+			// since the other side requested the selection.
+			// Let's respond with the property set to none.
+			None => return Ok(false),
+		};
+		match data_list.iter().find(|d| d.format == format) {
+			// Data that doesn't fit into a single request has to be sent in chunks.
+			Some(data) if data.bytes.len() > self.incr_chunk_len() => {
+				self.start_incr_transfer(
+					requestor,
+					target,
+					property,
+					data.bytes.clone(),
+					transfers,
+				)?;
+				Ok(true)
 			}
+			Some(data) => {
+				self.server
+					.conn
+					.change_property8(PropMode::REPLACE, requestor, property, target, &data.bytes)
+					.map_err(into_unknown)?;
+				Ok(true)
+			}
+			None => Ok(false),
 		}
+	}
+
+	/// Converts the selection to each of the targets that the requestor listed in pairs with the
+	/// property to write it into. The property of every pair that couldn't be converted is
+	/// replaced by `None`.
+	///
+	/// See: https://tronche.com/gui/x/icccm/sec-2.html#s-2.6.2
+	fn convert_multiple(
+		&self,
+		selection: LinuxClipboardKind,
+		event: &SelectionRequestEvent,
+		transfers: &mut Vec<IncrTransfer>,
+	) -> Result<bool> {
+		// Requestors have to name a property for the list, there's no default for it.
+		if event.property == NONE {
+			return Ok(false);
+		}
+		let reply = self
+			.server
+			.conn
+			.get_property(false, event.requestor, event.property, AtomEnum::ANY, 0, u32::MAX / 4)
+			.map_err(into_unknown)?
+			.reply()
+			.map_err(into_unknown)?;
+		let mut pairs: Vec<Atom> = match reply.value32() {
+			Some(values) => values.collect(),
+			None => return Ok(false),
+		};
+		trace!("Handling MULTIPLE with {} targets", pairs.len() / 2);
+
+		for pair in pairs.chunks_exact_mut(2) {
+			// A `MULTIPLE` request can't contain another one.
+			let converted = pair[0] != self.atoms.MULTIPLE
+				&& self.convert_target(selection, event.requestor, pair[0], pair[1], transfers)?;
+			if !converted {
+				pair[1] = NONE;
+			}
+		}
+		self.server
+			.conn
+			.change_property32(
+				PropMode::REPLACE,
+				event.requestor,
+				event.property,
+				self.atoms.ATOM_PAIR,
+				&pairs,
+			)
+			.map_err(into_unknown)?;
+		Ok(true)
+	}
+
+	fn handle_selection_request(
+		&self,
+		event: SelectionRequestEvent,
+		transfers: &mut Vec<IncrTransfer>,
+	) -> Result<()> {
+		let selection = match self.kind_of(event.selection) {
+			Some(kind) => kind,
+			None => {
+				warn!("Received a selection request to a selection other than the CLIPBOARD, PRIMARY or SECONDARY. This is unexpected.");
+				return Ok(());
+			}
+		};
+
+		let success = if event.target == self.atoms.MULTIPLE {
+			self.convert_multiple(selection, &event, transfers)?
+		} else {
+			self.convert_target(
+				selection,
+				event.requestor,
+				event.target,
+				event.property,
+				transfers,
+			)?
+		};
 		// on failure we notify the requester of it
 		let property = if success { event.property } else { AtomEnum::NONE.into() };
 		// tell the requestor that we finished sending data
@@ -656,7 +1178,12 @@ impl Inner {
 		self.server.conn.flush().map_err(into_unknown)
 	}
 
-	fn ask_clipboard_manager_to_request_our_data(&self) -> Result<()> {
+	/// Asks the clipboard manager to save the contents of the `Clipboard` selection, so that
+	/// they stay available after this process exits, and waits up to `timeout` for it to do so.
+	///
+	/// Returns [`Error::NoClipboardManager`] if there's no clipboard manager, or it didn't finish
+	/// in time.
+	fn hand_over(&self, timeout: Duration) -> Result<()> {
 		if self.server.win_id == 0 {
 			// This shouldn't really ever happen but let's just check.
 			error!("The server's window id was 0. This is unexpected");
@@ -672,6 +1199,18 @@ impl Inner {
 			return Ok(());
 		}
 
+		let manager = self
+			.server
+			.conn
+			.get_selection_owner(self.atoms.CLIPBOARD_MANAGER)
+			.map_err(into_unknown)?
+			.reply()
+			.map_err(into_unknown)?
+			.owner;
+		if manager == NONE {
+			return Err(Error::NoClipboardManager);
+		}
+
 		// It's important that we lock the state before sending the request
 		// because we don't want the request server thread to lock the state
 		// after the request but before we can lock it here.
@@ -690,19 +1229,19 @@ impl Inner {
 			.map_err(into_unknown)?;
 		self.server.conn.flush().map_err(into_unknown)?;
 
-		*handover_state = ManagerHandoverState::InProgress;
-		let max_handover_duration = Duration::from_millis(100);
+		*handover_state = ManagerHandoverState::InProgress { written: false, notified: false };
 
 		// Note that we are using a parking_lot condvar here, which doesn't wake up
 		// spuriously
-		let result = self.handover_cv.wait_for(&mut handover_state, max_handover_duration);
+		let result = self.handover_cv.wait_for(&mut handover_state, timeout);
 
 		if *handover_state == ManagerHandoverState::Finished {
 			return Ok(());
 		}
 		if result.timed_out() {
-			warn!("Could not hand the clipboard contents over to the clipboard manager. The request timed out.");
-			return Ok(());
+			// Whatever the clipboard manager still does belongs to no handover now.
+			*handover_state = ManagerHandoverState::Idle;
+			return Err(Error::NoClipboardManager);
 		}
 
 		Err(Error::Unknown {
@@ -728,11 +1267,23 @@ fn serve_requests(context: Arc<Inner>) -> Result<(), Box<dyn std::error::Error>>
 		context.serve_stopped.store(true, Ordering::Relaxed);
 	});
 
-	let mut written = false;
-	let mut notified = false;
+	let mut incr_transfers = Vec::new();
 
 	loop {
-		match context.server.conn.wait_for_event().map_err(into_unknown)? {
+		context.prune_incr_transfers(&mut incr_transfers).map_err(into_unknown)?;
+		// While data is sent with `INCR`, keep waking up to notice requestors that went away.
+		let event = if incr_transfers.is_empty() {
+			context.server.conn.wait_for_event().map_err(into_unknown)?
+		} else {
+			match context.server.conn.poll_for_event().map_err(into_unknown)? {
+				Some(event) => event,
+				None => {
+					std::thread::sleep(Duration::from_millis(1));
+					continue;
+				}
+			}
+		};
+		match event {
 			Event::DestroyNotify(_) => {
 				// This window is being destroyed.
 				trace!("Clipboard server window is being destroyed x_x");
@@ -765,22 +1316,29 @@ fn serve_requests(context: Arc<Inner>) -> Result<(), Box<dyn std::error::Error>>
 					context.atom_name_dbg(event.target),
 				);
 				// Someone is requesting the clipboard content from us.
-				context.handle_selection_request(event).map_err(into_unknown)?;
+				context
+					.handle_selection_request(event, &mut incr_transfers)
+					.map_err(into_unknown)?;
 
 				// if we are in the progress of saving to the clipboard manager
 				// make sure we save that we have finished writing
-				let handover_state = context.handover_state.lock();
-				if *handover_state == ManagerHandoverState::InProgress {
+				let mut handover_state = context.handover_state.lock();
+				let finished = match &mut *handover_state {
 					// Only set written, when the actual contents were written,
-					// not just a response to what TARGETS we have.
-					if event.target != context.atoms.TARGETS {
+					// not just a response to what TARGETS we have or when we got them.
+					ManagerHandoverState::InProgress { written, notified }
+						if event.target != context.atoms.TARGETS
+							&& event.target != context.atoms.TIMESTAMP =>
+					{
 						trace!("The contents were written to the clipboard manager.");
-						written = true;
+						*written = true;
 						// if we have written and notified, make sure to notify that we are done
-						if notified {
-							handover_finished(&context, handover_state);
-						}
+						*notified
 					}
+					_ => false,
+				};
+				if finished {
+					handover_finished(&context, handover_state);
 				}
 			}
 			Event::SelectionNotify(event) => {
@@ -792,25 +1350,33 @@ fn serve_requests(context: Arc<Inner>) -> Result<(), Box<dyn std::error::Error>>
 					error!("Received a `SelectionNotify` from a selection other than the CLIPBOARD_MANAGER. This is unexpected in this thread.");
 					continue;
 				}
-				let handover_state = context.handover_state.lock();
-				if *handover_state == ManagerHandoverState::InProgress {
-					// Note that some clipboard managers send a selection notify
-					// before even sending a request for the actual contents.
-					// (That's why we use the "notified" & "written" flags)
-					trace!("The clipboard manager indicated that it's done requesting the contents from us.");
-					notified = true;
-
-					// One would think that we could also finish if the property
-					// here is set 0, because that indicates failure. However
-					// this is not the case; for example on KDE plasma 5.18, we
-					// immediately get a SelectionNotify with property set to 0,
-					// but following that, we also get a valid SelectionRequest
-					// from the clipboard manager.
-					if written {
-						handover_finished(&context, handover_state);
+				let mut handover_state = context.handover_state.lock();
+				let finished = match &mut *handover_state {
+					ManagerHandoverState::InProgress { written, notified } => {
+						// Note that some clipboard managers send a selection notify
+						// before even sending a request for the actual contents.
+						// (That's why we use the "notified" & "written" flags)
+						trace!("The clipboard manager indicated that it's done requesting the contents from us.");
+						*notified = true;
+
+						// One would think that we could also finish if the property
+						// here is set 0, because that indicates failure. However
+						// this is not the case; for example on KDE plasma 5.18, we
+						// immediately get a SelectionNotify with property set to 0,
+						// but following that, we also get a valid SelectionRequest
+						// from the clipboard manager.
+						*written
 					}
+					_ => false,
+				};
+				if finished {
+					handover_finished(&context, handover_state);
 				}
 			}
+			// A requestor took a chunk of data that is sent with `INCR`.
+			Event::PropertyNotify(event) if event.state == Property::DELETE => {
+				context.send_incr_chunk(event, &mut incr_transfers).map_err(into_unknown)?;
+			}
 			_event => {
 				// May be useful for debugging but nothing else really.
 				// trace!("Received unwanted event: {:?}", event);
@@ -825,12 +1391,34 @@ pub(crate) struct Clipboard {
 
 impl Clipboard {
 	pub(crate) fn new() -> Result<Self> {
-		let mut global_cb = CLIPBOARD.lock();
-		if let Some(global_cb) = &*global_cb {
+		Self::with_display(None)
+	}
+
+	/// Connects to the X server of the given display, like `:1`, or the one named by `$DISPLAY`.
+	pub(crate) fn with_display(display: Option<&str>) -> Result<Self> {
+		Self::connect(display, true)
+	}
+
+	/// Connects to the X server without sharing the connection with any other instance, which
+	/// lets tests act as two separate clients.
+	#[cfg(test)]
+	pub(crate) fn separate_client(display: Option<&str>) -> Result<Self> {
+		Self::connect(display, false)
+	}
+
+	fn connect(display: Option<&str>, shared: bool) -> Result<Self> {
+		let mut global_cbs = CLIPBOARDS.lock();
+		// Different names, like `None` and the one in `$DISPLAY` or `:0` and `:0.0`, can refer to
+		// the same display. They must share a single window, or they would keep taking the
+		// selections away from each other.
+		let parsed_display = parse_display(display);
+		if let Some(global_cb) =
+			global_cbs.iter().find(|cb| shared && cb.shared && cb.display == parsed_display)
+		{
 			return Ok(Self { inner: Arc::clone(&global_cb.inner) });
 		}
 		// At this point we know that the clipboard does not exist.
-		let ctx = Arc::new(Inner::new()?);
+		let ctx = Arc::new(Inner::new(display)?);
 		let join_handle;
 		{
 			let ctx = Arc::clone(&ctx);
@@ -840,11 +1428,107 @@ impl Clipboard {
 				}
 			});
 		}
-		*global_cb = Some(GlobalClipboard { inner: Arc::clone(&ctx), server_handle: join_handle });
+		global_cbs.push(GlobalClipboard {
+			inner: Arc::clone(&ctx),
+			display: parsed_display,
+			shared,
+			server_handle: join_handle,
+		});
 		Ok(Self { inner: ctx })
 	}
 
-	pub(crate) fn get_text(&self, selection: LinuxClipboardKind) -> Result<String> {
+	pub(crate) fn connection(&self) -> (&RustConnection, Window) {
+		(&self.inner.server.conn, self.inner.server.win_id)
+	}
+
+	pub(crate) fn set_owner_name(&self, name: &str) -> Result<()> {
+		let conn = &self.inner.server.conn;
+		let win_id = self.inner.server.win_id;
+		let utf8 = self.inner.atoms.UTF8_STRING;
+
+		conn.change_property8(PropMode::REPLACE, win_id, AtomEnum::WM_NAME, utf8, name.as_bytes())
+			.map_err(into_unknown)?;
+		conn.change_property8(
+			PropMode::REPLACE,
+			win_id,
+			self.inner.atoms.NET_WM_NAME,
+			utf8,
+			name.as_bytes(),
+		)
+		.map_err(into_unknown)?;
+
+		// `WM_CLASS` consists of two NUL-terminated strings: the instance name and the class name.
+		// See: https://tronche.com/gui/x/icccm/sec-4.html#WM_CLASS
+		let class = format!("{0}\0{0}\0", name);
+		conn.change_property8(
+			PropMode::REPLACE,
+			win_id,
+			AtomEnum::WM_CLASS,
+			AtomEnum::STRING,
+			class.as_bytes(),
+		)
+		.map_err(into_unknown)?;
+
+		conn.flush().map_err(into_unknown)
+	}
+
+	pub(crate) fn clear(&self, selection: LinuxClipboardKind) -> Result<()> {
+		self.inner
+			.server
+			.conn
+			.set_selection_owner(NONE, self.inner.atom_of(selection), Time::CURRENT_TIME)
+			.map_err(into_unknown)?;
+		self.inner.server.conn.flush().map_err(into_unknown)?;
+
+		// The server thread is notified of this through a `SelectionClear` as well, but the data
+		// is dropped right away so that it can't be served in the meantime.
+		let selection = self.inner.selection_of(selection);
+		let mut data_guard = selection.data.write();
+		*data_guard = None;
+
+		// Wake up anyone waiting for the contents of this selection to be replaced.
+		let _guard = selection.mutex.lock();
+		selection.data_changed.notify_all();
+
+		Ok(())
+	}
+
+	pub(crate) fn hand_over(&self, timeout: Duration) -> Result<()> {
+		self.inner.hand_over(timeout)
+	}
+
+	pub(crate) fn is_owner(&self, selection: LinuxClipboardKind) -> Result<bool> {
+		self.inner.is_owner(selection)
+	}
+
+	pub(crate) fn available_selections(&self) -> Result<Vec<LinuxClipboardKind>> {
+		let mut available = Vec::new();
+		for selection in [
+			LinuxClipboardKind::Clipboard,
+			LinuxClipboardKind::Primary,
+			LinuxClipboardKind::Secondary,
+		] {
+			let owner = self
+				.inner
+				.server
+				.conn
+				.get_selection_owner(self.inner.atom_of(selection))
+				.map_err(into_unknown)?
+				.reply()
+				.map_err(into_unknown)?
+				.owner;
+			if owner != NONE {
+				available.push(selection);
+			}
+		}
+		Ok(available)
+	}
+
+	pub(crate) fn get_text(
+		&self,
+		selection: LinuxClipboardKind,
+		cancel: Option<&AtomicBool>,
+	) -> Result<String> {
 		let formats = [
 			self.inner.atoms.UTF8_STRING,
 			self.inner.atoms.UTF8_MIME_0,
@@ -853,12 +1537,12 @@ impl Clipboard {
 			self.inner.atoms.TEXT,
 			self.inner.atoms.TEXT_MIME_UNKNOWN,
 		];
-		let result = self.inner.read(&formats, selection)?;
+		let result = self.inner.read(&formats, selection, cancel)?;
 		if result.format == self.inner.atoms.STRING {
-			// ISO Latin-1
-			// See: https://stackoverflow.com/questions/28169745/what-are-the-options-to-convert-iso-8859-1-latin-1-to-a-string-utf-8
-			Ok(result.bytes.into_iter().map(|c| c as char).collect())
+			Ok(latin1_to_string(&result.bytes))
 		} else {
+			// `TEXT` and `text/plain` don't specify an encoding, but UTF-8 is a superset of ASCII
+			// and by far the most common choice in practice.
 			String::from_utf8(result.bytes).map_err(|_| Error::ConversionFailure)
 		}
 	}
@@ -868,12 +1552,13 @@ impl Clipboard {
 		message: Cow<'_, str>,
 		selection: LinuxClipboardKind,
 		wait: bool,
+		time: Option<Timestamp>,
 	) -> Result<()> {
-		let data = vec![ClipboardData {
-			bytes: message.into_owned().into_bytes(),
-			format: self.inner.atoms.UTF8_STRING,
-		}];
-		self.inner.write(data, selection, wait)
+		let data = vec![ClipboardData::new(
+			message.into_owned().into_bytes(),
+			self.inner.atoms.UTF8_STRING,
+		)];
+		self.inner.write(data, selection, wait, time)
 	}
 
 	pub(crate) fn set_html(
@@ -882,37 +1567,69 @@ impl Clipboard {
 		alt: Option<Cow<'_, str>>,
 		selection: LinuxClipboardKind,
 		wait: bool,
+		time: Option<Timestamp>,
 	) -> Result<()> {
 		let mut data = vec![];
 		if let Some(alt_text) = alt {
-			data.push(ClipboardData {
-				bytes: alt_text.into_owned().into_bytes(),
-				format: self.inner.atoms.UTF8_STRING,
-			});
+			data.push(ClipboardData::new(
+				alt_text.into_owned().into_bytes(),
+				self.inner.atoms.UTF8_STRING,
+			));
 		}
-		data.push(ClipboardData {
-			bytes: html.into_owned().into_bytes(),
-			format: self.inner.atoms.HTML,
-		});
-		self.inner.write(data, selection, wait)
+		data.push(ClipboardData::new(html.into_owned().into_bytes(), self.inner.atoms.HTML));
+		self.inner.write(data, selection, wait, time)
+	}
+
+	pub(crate) fn get_raw(
+		&self,
+		selection: LinuxClipboardKind,
+		target: &str,
+		cancel: Option<&AtomicBool>,
+	) -> Result<Vec<u8>> {
+		// A target that nobody has interned yet can't be offered by anyone either.
+		let atom = self
+			.inner
+			.server
+			.conn
+			.intern_atom(true, target.as_bytes())
+			.map_err(into_unknown)?
+			.reply()
+			.map_err(into_unknown)?
+			.atom;
+		if atom == NONE {
+			return Err(Error::ContentNotAvailable);
+		}
+		Ok(self.inner.read(&[atom], selection, cancel)?.bytes)
+	}
+
+	pub(crate) fn set_raw(
+		&self,
+		target: &str,
+		bytes: Vec<u8>,
+		selection: LinuxClipboardKind,
+		wait: bool,
+		time: Option<Timestamp>,
+	) -> Result<()> {
+		let format = self
+			.inner
+			.server
+			.conn
+			.intern_atom(false, target.as_bytes())
+			.map_err(into_unknown)?
+			.reply()
+			.map_err(into_unknown)?
+			.atom;
+		self.inner.write(vec![ClipboardData::new(bytes, format)], selection, wait, time)
 	}
 
 	#[cfg(feature = "image-data")]
-	pub(crate) fn get_image(&self, selection: LinuxClipboardKind) -> Result<ImageData<'static>> {
+	pub(crate) fn get_png(
+		&self,
+		selection: LinuxClipboardKind,
+		cancel: Option<&AtomicBool>,
+	) -> Result<Vec<u8>> {
 		let formats = [self.inner.atoms.PNG_MIME];
-		let bytes = self.inner.read(&formats, selection)?.bytes;
-
-		let cursor = std::io::Cursor::new(&bytes);
-		let mut reader = image::io::Reader::new(cursor);
-		reader.set_format(image::ImageFormat::Png);
-		let image = match reader.decode() {
-			Ok(img) => img.into_rgba8(),
-			Err(_e) => return Err(Error::ConversionFailure),
-		};
-		let (w, h) = image.dimensions();
-		let image_data =
-			ImageData { width: w as usize, height: h as usize, bytes: image.into_raw().into() };
-		Ok(image_data)
+		Ok(self.inner.read(&formats, selection, cancel)?.bytes)
 	}
 
 	#[cfg(feature = "image-data")]
@@ -921,13 +1638,33 @@ impl Clipboard {
 		image: ImageData,
 		selection: LinuxClipboardKind,
 		wait: bool,
+		time: Option<Timestamp>,
+		indexed_png: bool,
 	) -> Result<()> {
-		let encoded = encode_as_png(&image)?;
-		let data = vec![ClipboardData { bytes: encoded, format: self.inner.atoms.PNG_MIME }];
-		self.inner.write(data, selection, wait)
+		if image.bytes.is_empty() || image.width == 0 || image.height == 0 {
+			return Err(Error::ConversionFailure);
+		}
+
+		// Encoding is left to whoever requests the image first, as it's expensive and wasted on
+		// images that are replaced before anyone pastes them.
+		let data = vec![ClipboardData {
+			bytes: Vec::new(),
+			format: self.inner.atoms.PNG_MIME,
+			unencoded_image: Some(Arc::new(image.to_owned_img())),
+			indexed_png,
+		}];
+		self.inner.write(data, selection, wait, time)
 	}
 }
 
+/// Decodes ISO Latin-1 text, the encoding of the `STRING` target.
+///
+/// Every Latin-1 byte has the same value as the Unicode code point it represents.
+/// See: https://stackoverflow.com/questions/28169745/what-are-the-options-to-convert-iso-8859-1-latin-1-to-a-string-utf-8
+fn latin1_to_string(bytes: &[u8]) -> String {
+	bytes.iter().map(|&c| c as char).collect()
+}
+
 impl Drop for Clipboard {
 	fn drop(&mut self) {
 		// There are always at least 3 owners:
@@ -936,16 +1673,28 @@ impl Drop for Clipboard {
 
 		// We start with locking the global guard to prevent race
 		// conditions below.
-		let mut global_cb = CLIPBOARD.lock();
+		let mut global_cbs = CLIPBOARDS.lock();
 		if Arc::strong_count(&self.inner) == MIN_OWNERS {
 			// If the are the only owners of the clipboard are ourselves and
 			// the global object, then we should destroy the global object,
 			// and send the data to the clipboard manager
 
-			if let Err(e) = self.inner.ask_clipboard_manager_to_request_our_data() {
-				error!("Could not hand the clipboard data over to the clipboard manager: {}", e);
+			match self.inner.hand_over(Duration::from_millis(100)) {
+				Ok(()) => {}
+				Err(Error::NoClipboardManager) => {
+					warn!("Could not hand the clipboard contents over to the clipboard manager. There is none, or the request timed out.");
+				}
+				Err(e) => {
+					error!(
+						"Could not hand the clipboard data over to the clipboard manager: {}",
+						e
+					);
+				}
 			}
-			let global_cb = global_cb.take();
+			let global_cb = global_cbs
+				.iter()
+				.position(|cb| Arc::ptr_eq(&cb.inner, &self.inner))
+				.map(|index| global_cbs.swap_remove(index));
 			if let Err(e) = self.inner.server.conn.destroy_window(self.inner.server.win_id) {
 				error!("Failed to destroy the clipboard window. Error: {}", e);
 				return;
@@ -978,3 +1727,14 @@ impl Drop for Clipboard {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::latin1_to_string;
+
+	#[test]
+	fn decode_latin1_string_target() {
+		let bytes = [b'a', b'b', 0x80, 0xA9, 0xE9, 0xFF];
+		assert_eq!(latin1_to_string(&bytes), "ab\u{80}\u{A9}\u{E9}\u{FF}");
+	}
+}
@@ -8,14 +8,17 @@ the Apache 2.0 or the MIT license at the licensee's choice. The terms
 and conditions of the chosen license apply to this file.
 */
 
-use crate::common::Error;
 #[cfg(feature = "image-data")]
-use crate::common::ImageData;
+use crate::common::{
+	check_image_size, decode_rgba, ImageAttempts, ImageData, ImageMetadata,
+	DEFAULT_MAX_IMAGE_PIXELS,
+};
+use crate::common::{private, Error};
 #[cfg(feature = "image-data")]
 use core_graphics::{
 	base::{kCGBitmapByteOrderDefault, kCGImageAlphaLast, kCGRenderingIntentDefault, CGFloat},
 	color_space::CGColorSpace,
-	data_provider::{CGDataProvider, CustomData},
+	data_provider::CGDataProvider,
 	image::CGImage,
 };
 use objc::{
@@ -23,21 +26,64 @@ use objc::{
 	runtime::{Class, Object},
 	sel, sel_impl,
 };
-use objc_foundation::{INSArray, INSObject, INSString, NSArray, NSDictionary, NSObject, NSString};
+use objc_foundation::{
+	INSArray, INSData, INSObject, INSString, NSArray, NSData, NSDictionary, NSObject, NSString,
+};
 use objc_id::{Id, Owned};
 use once_cell::sync::Lazy;
 use std::borrow::Cow;
+#[cfg(feature = "image-data")]
+use std::{path::Path, sync::Arc};
 
 // Required to bring NSPasteboard into the path of the class-resolver
 #[link(name = "AppKit", kind = "framework")]
 extern "C" {
 	static NSPasteboardTypeHTML: *const Object;
 	static NSPasteboardTypeString: *const Object;
+	#[cfg(feature = "image-data")]
+	static NSImageColorSyncProfileData: *const Object;
 }
 
+static NSPASTEBOARD_CLASS: Lazy<&Class> =
+	Lazy::new(|| Class::get("NSPasteboard").expect("NSPasteboard not registered"));
 static NSSTRING_CLASS: Lazy<&Class> = Lazy::new(|| Class::get("NSString").unwrap());
+static NSATTRIBUTEDSTRING_CLASS: Lazy<&Class> =
+	Lazy::new(|| Class::get("NSAttributedString").unwrap());
 #[cfg(feature = "image-data")]
 static NSIMAGE_CLASS: Lazy<&Class> = Lazy::new(|| Class::get("NSImage").unwrap());
+#[cfg(feature = "image-data")]
+static NSBITMAPIMAGEREP_CLASS: Lazy<&Class> = Lazy::new(|| Class::get("NSBitmapImageRep").unwrap());
+#[cfg(feature = "image-data")]
+static NSURL_CLASS: Lazy<&Class> = Lazy::new(|| Class::get("NSURL").unwrap());
+
+#[cfg(feature = "image-data")]
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct NSSize {
+	width: CGFloat,
+	height: CGFloat,
+}
+
+/// Returns the ICC profile of the first bitmap representation of an `NSImage`, if it has one.
+#[cfg(feature = "image-data")]
+fn icc_profile(image: &NSObject) -> Option<Vec<u8>> {
+	let representations: *const NSArray<NSObject> = unsafe { msg_send![image, representations] };
+	if representations.is_null() {
+		return None;
+	}
+	// SAFETY: The array was checked to not be null and is kept alive by `image`.
+	let representations = unsafe { &*representations };
+
+	let bitmap =
+		representations.to_vec().into_iter().find(|rep| rep.is_kind_of(&NSBITMAPIMAGEREP_CLASS))?;
+	let profile: *const NSData =
+		unsafe { msg_send![bitmap, valueForProperty: NSImageColorSyncProfileData] };
+	if profile.is_null() {
+		return None;
+	}
+	// SAFETY: The data was checked to not be null and is kept alive by the representation.
+	Some(unsafe { &*profile }.bytes().to_vec())
+}
 
 /// Returns an NSImage object on success.
 #[cfg(feature = "image-data")]
@@ -46,30 +92,9 @@ fn image_from_pixels(
 	width: usize,
 	height: usize,
 ) -> Result<Id<NSObject>, Box<dyn std::error::Error>> {
-	#[repr(C)]
-	#[derive(Copy, Clone)]
-	struct NSSize {
-		width: CGFloat,
-		height: CGFloat,
-	}
-
-	#[derive(Debug)]
-	struct PixelArray {
-		data: Vec<u8>,
-	}
-
-	impl CustomData for PixelArray {
-		unsafe fn ptr(&self) -> *const u8 {
-			self.data.as_ptr()
-		}
-		unsafe fn len(&self) -> usize {
-			self.data.len()
-		}
-	}
-
 	let colorspace = CGColorSpace::create_device_rgb();
-	let pixel_data: Box<Box<dyn CustomData>> = Box::new(Box::new(PixelArray { data: pixels }));
-	let provider = unsafe { CGDataProvider::from_custom_data(pixel_data) };
+	// The provider keeps the pixels alive for as long as the image needs them, without copying.
+	let provider = CGDataProvider::from_buffer(Arc::new(pixels));
 
 	let cg_image = CGImage::new(
 		width,
@@ -95,17 +120,19 @@ fn image_from_pixels(
 
 pub(crate) struct Clipboard {
 	pasteboard: Id<Object>,
+	/// The change count of the pasteboard right after this instance last cleared it, which is
+	/// how every write starts.
+	last_change_count: Option<usize>,
 }
 
 impl Clipboard {
 	pub(crate) fn new() -> Result<Clipboard, Error> {
-		let cls = Class::get("NSPasteboard").expect("NSPasteboard not registered");
-		let pasteboard: *mut Object = unsafe { msg_send![cls, generalPasteboard] };
+		let pasteboard: *mut Object = unsafe { msg_send![*NSPASTEBOARD_CLASS, generalPasteboard] };
 
 		if !pasteboard.is_null() {
 			// SAFETY: `generalPasteboard` is not null and a valid object pointer.
 			let pasteboard: Id<Object> = unsafe { Id::from_ptr(pasteboard) };
-			Ok(Clipboard { pasteboard })
+			Ok(Clipboard { pasteboard, last_change_count: None })
 		} else {
 			// Rust only supports 10.7+, while `generalPasteboard` first appeared in 10.0, so this
 			// is unreachable in "normal apps". However in some edge cases, like running under
@@ -115,7 +142,35 @@ impl Clipboard {
 	}
 
 	fn clear(&mut self) {
-		let _: usize = unsafe { msg_send![self.pasteboard, clearContents] };
+		let change_count: usize = unsafe { msg_send![self.pasteboard, clearContents] };
+		self.last_change_count = Some(change_count);
+	}
+
+	pub(crate) fn owner_is_self(&mut self) -> Result<bool, Error> {
+		// Only clearing the pasteboard changes the count, writing to it afterwards doesn't.
+		let change_count: usize = unsafe { msg_send![self.pasteboard, changeCount] };
+		Ok(self.last_change_count == Some(change_count))
+	}
+
+	fn state(&self) -> (u64, Vec<String>) {
+		// The types may change while they are listed, in which case they're listed again. The
+		// change count is the same before and after listing them only if they belong together.
+		loop {
+			let change_count: usize = unsafe { msg_send![self.pasteboard, changeCount] };
+			let types: *const NSArray<NSString> = unsafe { msg_send![self.pasteboard, types] };
+			let types = if types.is_null() {
+				Vec::new()
+			} else {
+				// SAFETY: The array was checked to not be null, and is autoreleased so it outlives
+				// this iteration.
+				let types = unsafe { &*types };
+				types.to_vec().into_iter().map(|t| t.as_str().to_owned()).collect()
+			};
+			let new_change_count: usize = unsafe { msg_send![self.pasteboard, changeCount] };
+			if new_change_count == change_count {
+				return (change_count as u64, types);
+			}
+		}
 	}
 
 	// fn get_binary_contents(&mut self) -> Result<Option<ClipboardContent>, Box<dyn std::error::Error>> {
@@ -169,6 +224,78 @@ impl Clipboard {
 	// }
 }
 
+/// macOS-specific extensions to the [`Clipboard`](crate::Clipboard) type.
+pub trait ClipboardExtMacOS: private::Sealed {
+	/// Returns a pointer to the `NSPasteboard` object used by arboard, which is the general
+	/// pasteboard.
+	///
+	/// The pointer stays valid for as long as the `Clipboard` exists. Ownership is not
+	/// transferred, so the object must not be released through it.
+	fn pasteboard_ptr(&self) -> *mut std::ffi::c_void;
+
+	/// Returns the pasteboard's change count together with the types of the data on it, like
+	/// `public.utf8-plain-text`, both read at the same point in time.
+	///
+	/// The change count increases every time the pasteboard's contents are replaced. Comparing it
+	/// with a later call tells whether data read in between, for example with
+	/// [`get_content_for_raw_type`](crate::Clipboard::get_content_for_raw_type), belongs to the
+	/// same contents as the types.
+	fn get_state(&mut self) -> Result<(u64, Vec<String>), Error>;
+
+	/// Fetches every bitmap representation of the image on the clipboard, like each page of a
+	/// multi-page TIFF or each resolution of an icon, and returns their decoded pixels.
+	///
+	/// Unlike [`get_image`](crate::Clipboard::get_image), which only ever returns a single image,
+	/// this returns one entry per representation.
+	#[cfg(feature = "image-data")]
+	fn get_images(&mut self) -> Result<Vec<ImageData<'static>>, Error>;
+
+	/// Fetches the image on the clipboard at the resolution closest to `max_dimension`.
+	///
+	/// Images like Retina screenshots hold the same picture at several resolutions. This picks
+	/// the smallest one whose width or height is at least `max_dimension` pixels, or the largest
+	/// one if none is that big, which saves decoding a full-size image just to show a thumbnail.
+	/// [`get_image`](crate::Clipboard::get_image) always returns the largest one.
+	#[cfg(feature = "image-data")]
+	fn get_image_at_scale(&mut self, max_dimension: u32) -> Result<ImageData<'static>, Error>;
+
+	/// Places an image onto the clipboard together with a file URL pointing to `path`, which
+	/// should be a file holding the same image.
+	///
+	/// Both are written at once, so that pasting into Finder copies the file while image editors
+	/// get the pixels. A relative `path` is resolved against the current directory, and must
+	/// exist.
+	#[cfg(feature = "image-data")]
+	fn set_image_with_file(&mut self, image: ImageData, path: &Path) -> Result<(), Error>;
+}
+
+impl ClipboardExtMacOS for crate::Clipboard {
+	fn pasteboard_ptr(&self) -> *mut std::ffi::c_void {
+		&*self.platform.pasteboard as *const Object as *mut std::ffi::c_void
+	}
+
+	fn get_state(&mut self) -> Result<(u64, Vec<String>), Error> {
+		Ok(self.platform.state())
+	}
+
+	#[cfg(feature = "image-data")]
+	fn get_images(&mut self) -> Result<Vec<ImageData<'static>>, Error> {
+		Get::new(&mut self.platform).images(DEFAULT_MAX_IMAGE_PIXELS)
+	}
+
+	#[cfg(feature = "image-data")]
+	fn get_image_at_scale(&mut self, max_dimension: u32) -> Result<ImageData<'static>, Error> {
+		Get::new(&mut self.platform)
+			.image_at_size(DEFAULT_MAX_IMAGE_PIXELS, Some(max_dimension))
+			.map(|(image, _)| image)
+	}
+
+	#[cfg(feature = "image-data")]
+	fn set_image_with_file(&mut self, image: ImageData, path: &Path) -> Result<(), Error> {
+		Set::new(&mut self.platform).image_with_file(image, path)
+	}
+}
+
 pub(crate) struct Get<'clipboard> {
 	pasteboard: &'clipboard Object,
 }
@@ -179,31 +306,179 @@ impl<'clipboard> Get<'clipboard> {
 	}
 
 	pub(crate) fn text(self) -> Result<String, Error> {
+		// Text that is advertised as UTF-8 is taken as-is, since it may not be readable as an
+		// `NSString` object.
+		if let Some(text) = self
+			.data_for_type("public.utf8-plain-text")
+			.and_then(|data| String::from_utf8(data).ok())
+		{
+			return Ok(text);
+		}
+
 		let string_class = object_class(&NSSTRING_CLASS);
 		let classes: Id<NSArray<NSObject, Owned>> = NSArray::from_vec(vec![string_class]);
 		let options: Id<NSDictionary<NSObject, NSObject>> = NSDictionary::new();
 
-		let string_array: Id<NSArray<NSString>> = unsafe {
+		let string_array: Option<Id<NSArray<NSString>>> = unsafe {
 			let obj: *mut NSArray<NSString> =
 				msg_send![self.pasteboard, readObjectsForClasses:&*classes options:&*options];
 
 			if obj.is_null() {
-				return Err(Error::ContentNotAvailable);
+				None
 			} else {
-				Id::from_ptr(obj)
+				Some(Id::from_ptr(obj))
 			}
 		};
 
-		string_array
-			.first_object()
-			.map(|obj| obj.as_str().to_owned())
-			.ok_or(Error::ContentNotAvailable)
+		match string_array.as_ref().and_then(|array| array.first_object()) {
+			Some(obj) => Ok(obj.as_str().to_owned()),
+			// Applications may only provide rich text, which still holds plain text underneath.
+			None => self.attributed_text().ok_or(Error::ContentNotAvailable),
+		}
+	}
+
+	/// Reads rich text, such as RTF, from the pasteboard and flattens it into plain text.
+	fn attributed_text(&self) -> Option<String> {
+		let attributed_string_class = object_class(&NSATTRIBUTEDSTRING_CLASS);
+		let classes: Id<NSArray<NSObject, Owned>> =
+			NSArray::from_vec(vec![attributed_string_class]);
+		let options: Id<NSDictionary<NSObject, NSObject>> = NSDictionary::new();
+
+		let attributed_array: Id<NSArray<NSObject>> = unsafe {
+			let obj: *mut NSArray<NSObject> =
+				msg_send![self.pasteboard, readObjectsForClasses:&*classes options:&*options];
+			if obj.is_null() {
+				return None;
+			}
+			Id::from_ptr(obj)
+		};
+
+		let attributed_string = attributed_array.first_object()?;
+		let string: *const NSString = unsafe { msg_send![attributed_string, string] };
+		if string.is_null() {
+			return None;
+		}
+		// SAFETY: The string was checked to not be null and is kept alive by `attributed_string`.
+		Some(unsafe { &*string }.as_str().to_owned())
+	}
+
+	pub(crate) fn text_with_locale(self) -> Result<(String, Option<String>), Error> {
+		self.text().map(|text| (text, None))
+	}
+
+	pub(crate) fn raw(self, system_type: &str) -> Result<Vec<u8>, Error> {
+		self.data_for_type(system_type).ok_or(Error::ContentNotAvailable)
+	}
+
+	fn data_for_type(&self, system_type: &str) -> Option<Vec<u8>> {
+		let system_type = NSString::from_str(system_type);
+		let data: *const NSData = unsafe { msg_send![self.pasteboard, dataForType: &*system_type] };
+		if data.is_null() {
+			return None;
+		}
+		// SAFETY: The data was checked to not be null, and is autoreleased so it outlives this call.
+		Some(unsafe { &*data }.bytes().to_vec())
 	}
 
 	#[cfg(feature = "image-data")]
-	pub(crate) fn image(self) -> Result<ImageData<'static>, Error> {
-		use std::io::Cursor;
+	pub(crate) fn image(self, max_pixels: u64) -> Result<ImageData<'static>, Error> {
+		self.image_with_metadata(max_pixels).map(|(image, _)| image)
+	}
+
+	#[cfg(feature = "image-data")]
+	pub(crate) fn image_with_metadata(
+		self,
+		max_pixels: u64,
+	) -> Result<(ImageData<'static>, ImageMetadata), Error> {
+		self.image_at_size(max_pixels, None)
+	}
+
+	/// Reads the image representation picked by [`pick_representation`] for `max_dimension`.
+	#[cfg(feature = "image-data")]
+	fn image_at_size(
+		self,
+		max_pixels: u64,
+		max_dimension: Option<u32>,
+	) -> Result<(ImageData<'static>, ImageMetadata), Error> {
+		/// The resolution at which one pixel corresponds to one point.
+		const POINT_DPI: CGFloat = 72.0;
+
+		let mut attempts = ImageAttempts::default();
+
+		let tiff = self.with_image(|image| {
+			let bitmaps = bitmap_representations(image);
+			let sizes: Vec<(u64, u64)> = bitmaps.iter().copied().map(pixel_size).collect();
+
+			// Images without any bitmaps, like PDFs, are rendered by `NSImage` itself instead.
+			let tiff: *const Object = match pick_representation(&sizes, max_dimension) {
+				Some(index) => {
+					// Don't have a TIFF of an image that's too large created in the first place.
+					let (width, height) = sizes[index];
+					check_image_size(width, height, max_pixels)?;
+					unsafe { msg_send![bitmaps[index], TIFFRepresentation] }
+				}
+				None => unsafe { msg_send![image, TIFFRepresentation] },
+			};
+			let image_data = image_from_tiff(tiff, max_pixels)?;
+
+			// The size of an `NSImage` is given in points, regardless of how many pixels it has.
+			let size: NSSize = unsafe { msg_send![image, size] };
+			let dpi = if size.width > 0.0 && size.height > 0.0 {
+				Some((
+					image_data.width as CGFloat / size.width * POINT_DPI,
+					image_data.height as CGFloat / size.height * POINT_DPI,
+				))
+			} else {
+				None
+			};
+
+			Ok((
+				image_data,
+				ImageMetadata { dpi, icc_profile: icc_profile(image), ..Default::default() },
+			))
+		});
+		if let Some(image) = attempts.record("tiff", tiff)? {
+			return Ok(image);
+		}
 
+		// Older versions of macOS can't read WebP images, which browsers may only offer.
+		#[cfg(feature = "image-webp")]
+		{
+			let webp = self
+				.data_for_type("org.webmproject.webp")
+				.ok_or(Error::ContentNotAvailable)
+				.and_then(|webp| decode_rgba(&webp, image::ImageFormat::WebP, max_pixels));
+			if let Some(image_data) = attempts.record("webp", webp)? {
+				return Ok((image_data, ImageMetadata::default()));
+			}
+		}
+
+		Err(attempts.into_error())
+	}
+
+	#[cfg(feature = "image-data")]
+	pub(crate) fn images(self, max_pixels: u64) -> Result<Vec<ImageData<'static>>, Error> {
+		self.with_image(|image| {
+			let images = bitmap_representations(image)
+				.into_iter()
+				.map(|rep| {
+					let (width, height) = pixel_size(rep);
+					check_image_size(width, height, max_pixels)?;
+					let tiff: *const Object = unsafe { msg_send![rep, TIFFRepresentation] };
+					image_from_tiff(tiff, max_pixels)
+				})
+				.collect::<Result<Vec<_>, _>>()?;
+
+			if images.is_empty() {
+				return Err(Error::ContentNotAvailable);
+			}
+			Ok(images)
+		})
+	}
+
+	/// Reads the first `NSImage` from the pasteboard and passes it to `f`.
+	#[cfg(feature = "image-data")]
+	fn with_image<T>(&self, f: impl FnOnce(&NSObject) -> Result<T, Error>) -> Result<T, Error> {
 		let image_class: Id<NSObject> = object_class(&NSIMAGE_CLASS);
 		let classes = vec![image_class];
 		let classes: Id<NSArray<NSObject, Owned>> = NSArray::from_vec(classes);
@@ -220,33 +495,113 @@ impl<'clipboard> Get<'clipboard> {
 			}
 		};
 
-		let obj = match contents.first_object() {
-			Some(obj) if obj.is_kind_of(&NSIMAGE_CLASS) => obj,
-			Some(_) | None => return Err(Error::ContentNotAvailable),
-		};
+		match contents.first_object() {
+			Some(obj) if obj.is_kind_of(&NSIMAGE_CLASS) => f(obj),
+			Some(_) | None => Err(Error::ContentNotAvailable),
+		}
+	}
+}
 
-		let tiff: &NSArray<NSObject> = unsafe { msg_send![obj, TIFFRepresentation] };
-		let data = unsafe {
-			let len: usize = msg_send![tiff, length];
-			let bytes: *const u8 = msg_send![tiff, bytes];
+/// Returns the bitmap representations of an `NSImage`, in order.
+///
+/// Only bitmaps can be turned into a TIFF on their own, other representations (like PDF or EPS
+/// ones) are skipped.
+#[cfg(feature = "image-data")]
+fn bitmap_representations(image: &NSObject) -> Vec<&NSObject> {
+	let representations: *const NSArray<NSObject> = unsafe { msg_send![image, representations] };
+	if representations.is_null() {
+		return Vec::new();
+	}
+	// SAFETY: The array was checked to not be null and is kept alive by `image`.
+	let representations = unsafe { &*representations };
+	representations
+		.to_vec()
+		.into_iter()
+		.filter(|rep| rep.is_kind_of(&NSBITMAPIMAGEREP_CLASS))
+		.collect()
+}
 
-			Cursor::new(std::slice::from_raw_parts(bytes, len))
-		};
-		let reader = image::io::Reader::with_format(data, image::ImageFormat::Tiff);
-		match reader.decode() {
-			Ok(img) => {
-				let rgba = img.into_rgba8();
-				let (width, height) = rgba.dimensions();
-
-				Ok(ImageData {
-					width: width as usize,
-					height: height as usize,
-					bytes: rgba.into_raw().into(),
-				})
-			}
-			Err(_) => Err(Error::ConversionFailure),
-		}
+/// Returns the width and height of a bitmap representation, in pixels.
+#[cfg(feature = "image-data")]
+fn pixel_size(rep: &NSObject) -> (u64, u64) {
+	let width: isize = unsafe { msg_send![rep, pixelsWide] };
+	let height: isize = unsafe { msg_send![rep, pixelsHigh] };
+	(width.max(0) as u64, height.max(0) as u64)
+}
+
+/// Picks which of the representations with the given pixel sizes to read.
+///
+/// That's the smallest one whose larger side is at least `max_dimension`, falling back to the
+/// largest one when none are big enough or no size was asked for. Ties go to the first one.
+#[cfg(feature = "image-data")]
+fn pick_representation(sizes: &[(u64, u64)], max_dimension: Option<u32>) -> Option<usize> {
+	let dimension = |index: usize| sizes[index].0.max(sizes[index].1);
+	let largest = (0..sizes.len()).rev().max_by_key(|&index| dimension(index))?;
+
+	let wanted = match max_dimension {
+		Some(wanted) => u64::from(wanted),
+		None => return Some(largest),
+	};
+	let fitting = (0..sizes.len())
+		.filter(|&index| dimension(index) >= wanted)
+		.min_by_key(|&index| dimension(index));
+	Some(fitting.unwrap_or(largest))
+}
+
+/// Decodes the `NSData` object holding a TIFF image, as returned by `TIFFRepresentation`.
+#[cfg(feature = "image-data")]
+fn image_from_tiff(tiff: *const Object, max_pixels: u64) -> Result<ImageData<'static>, Error> {
+	if tiff.is_null() {
+		return Err(Error::ConversionFailure);
+	}
+
+	let data = unsafe {
+		let len: usize = msg_send![tiff, length];
+		let bytes: *const u8 = msg_send![tiff, bytes];
+
+		std::slice::from_raw_parts(bytes, len)
+	};
+	if let Some(image) = image_from_cmyk_tiff(data, max_pixels) {
+		return image;
+	}
+	decode_rgba(data, image::ImageFormat::Tiff, max_pixels)
+}
+
+/// Decodes a TIFF image with 8-bit CMYK samples into RGBA, returning `None` for any other TIFF.
+///
+/// The `image` crate can't decode CMYK images, which print workflows commonly produce. They're
+/// converted without color management, as the complement of each ink darkened by the black ink.
+#[cfg(feature = "image-data")]
+fn image_from_cmyk_tiff(tiff: &[u8], max_pixels: u64) -> Option<Result<ImageData<'static>, Error>> {
+	use tiff::{decoder::DecodingResult, ColorType};
+
+	let mut decoder = tiff::decoder::Decoder::new(std::io::Cursor::new(tiff)).ok()?;
+	if decoder.colortype().ok()? != ColorType::CMYK(8) {
+		return None;
 	}
+
+	let (width, height) = match decoder.dimensions() {
+		Ok(dimensions) => dimensions,
+		Err(_) => return Some(Err(Error::ConversionFailure)),
+	};
+	if let Err(e) = check_image_size(width.into(), height.into(), max_pixels) {
+		return Some(Err(e));
+	}
+	let cmyk = match decoder.read_image() {
+		Ok(DecodingResult::U8(cmyk)) => cmyk,
+		_ => return Some(Err(Error::ConversionFailure)),
+	};
+
+	let ink = |color: u8, black: u8| {
+		let (color, black) = (u16::from(color), u16::from(black));
+		((255 - color) * (255 - black) / 255) as u8
+	};
+	let rgba = cmyk
+		.chunks_exact(4)
+		.flat_map(|px| [ink(px[0], px[3]), ink(px[1], px[3]), ink(px[2], px[3]), 255])
+		.collect();
+
+	Some(Ok(ImageData { width: width as usize, height: height as usize, bytes: rgba }))
 }
 
 pub(crate) struct Set<'clipboard> {
@@ -307,6 +662,54 @@ impl<'clipboard> Set<'clipboard> {
 		}
 	}
 
+	pub(crate) fn raw(self, system_type: &str, data: Vec<u8>) -> Result<(), Error> {
+		self.clipboard.clear();
+
+		let system_type = NSString::from_str(system_type);
+		let data = NSData::from_vec(data);
+		let success: bool =
+			unsafe { msg_send![self.clipboard.pasteboard, setData: &*data forType: &*system_type] };
+		if success {
+			Ok(())
+		} else {
+			Err(Error::Unknown {
+				description: "NSPasteboard#setData:forType: returned false".into(),
+			})
+		}
+	}
+
+	#[cfg(feature = "image-data")]
+	pub(crate) fn image_with_file(self, data: ImageData, path: &Path) -> Result<(), Error> {
+		// File URLs have to be absolute, the percent-encoding is taken care of by `NSURL`.
+		let path = std::fs::canonicalize(path)
+			.map_err(|e| Error::Unknown { description: format!("invalid image path: {}", e) })?;
+		let path = NSString::from_str(path.to_str().ok_or(Error::ConversionFailure)?);
+		let url: *mut NSObject = unsafe { msg_send![*NSURL_CLASS, fileURLWithPath: &*path] };
+		if url.is_null() {
+			return Err(Error::ConversionFailure);
+		}
+		// SAFETY: The URL was checked to not be null, and is retained by `from_ptr`.
+		let url: Id<NSObject> = unsafe { Id::from_ptr(url) };
+
+		let pixels = data.bytes.into();
+		let image = image_from_pixels(pixels, data.width, data.height)
+			.map_err(|_| Error::ConversionFailure)?;
+
+		self.clipboard.clear();
+
+		let objects: Id<NSArray<NSObject, Owned>> = NSArray::from_vec(vec![image, url]);
+		let success: bool = unsafe { msg_send![self.clipboard.pasteboard, writeObjects: objects] };
+		if success {
+			Ok(())
+		} else {
+			Err(Error::Unknown {
+				description:
+					"Failed to write the image to the pasteboard (`writeObjects` returned NO)."
+						.into(),
+			})
+		}
+	}
+
 	#[cfg(feature = "image-data")]
 	pub(crate) fn image(self, data: ImageData) -> Result<(), Error> {
 		let pixels = data.bytes.into();
@@ -350,3 +753,54 @@ fn object_class(class: &'static Class) -> Id<NSObject> {
 	// SAFETY: `Class` is a valid object and `Id` will not mutate it
 	unsafe { Id::from_ptr(class as *const Class as *mut NSObject) }
 }
+
+#[cfg(all(test, feature = "image-data"))]
+mod tests {
+	use super::{image_from_cmyk_tiff, pick_representation};
+	use crate::Error;
+
+	#[test]
+	fn pick_image_representation() {
+		let sizes = [(64, 64), (512, 256), (128, 128), (1024, 512), (512, 512)];
+
+		assert_eq!(pick_representation(&sizes, None), Some(3));
+		assert_eq!(pick_representation(&sizes, Some(100)), Some(2));
+		assert_eq!(pick_representation(&sizes, Some(128)), Some(2));
+		// The first of two equally large representations wins.
+		assert_eq!(pick_representation(&sizes, Some(300)), Some(1));
+		assert_eq!(pick_representation(&sizes, Some(4096)), Some(3));
+		assert_eq!(pick_representation(&[], None), None);
+	}
+
+	#[test]
+	fn decode_cmyk_tiff() {
+		use tiff::encoder::{colortype::CMYK8, TiffEncoder};
+
+		#[rustfmt::skip]
+		let swatches = [
+			0, 0, 0, 0,       // paper
+			255, 0, 0, 0,     // cyan
+			0, 255, 255, 0,   // magenta and yellow
+			0, 0, 0, 255,     // black
+			0, 128, 255, 0,   // half magenta and yellow
+			0, 0, 0, 128,     // half black
+		];
+		let mut tiff = std::io::Cursor::new(Vec::new());
+		TiffEncoder::new(&mut tiff).unwrap().write_image::<CMYK8>(3, 2, &swatches).unwrap();
+		let tiff = tiff.into_inner();
+
+		let image = image_from_cmyk_tiff(&tiff, u64::MAX).unwrap().unwrap();
+		assert_eq!((image.width, image.height), (3, 2));
+		#[rustfmt::skip]
+		assert_eq!(image.bytes.as_ref(), [
+			255, 255, 255, 255,
+			0, 255, 255, 255,
+			255, 0, 0, 255,
+			0, 0, 0, 255,
+			255, 127, 0, 255,
+			127, 127, 127, 255,
+		]);
+
+		assert!(matches!(image_from_cmyk_tiff(&tiff, 5), Some(Err(Error::TooLarge))));
+	}
+}
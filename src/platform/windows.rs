@@ -8,39 +8,45 @@ the Apache 2.0 or the MIT license at the licensee's choice. The terms
 and conditions of the chosen license apply to this file.
 */
 
-use std::{borrow::Cow, marker::PhantomData};
+use std::{borrow::Cow, marker::PhantomData, num::NonZeroU32};
 #[cfg(feature = "image-data")]
 use std::{convert::TryInto, mem::size_of};
 
 #[cfg(feature = "image-data")]
 use winapi::{
-	shared::minwindef::DWORD,
+	shared::{
+		minwindef::{DWORD, FALSE, HGLOBAL},
+		windef::{HBITMAP, HPALETTE},
+	},
 	um::{
 		errhandlingapi::GetLastError,
-		winbase::{GlobalLock, GlobalUnlock},
+		winbase::{GlobalFree, GlobalLock, GlobalUnlock},
 		wingdi::{
-			CreateDIBitmap, DeleteObject, GetDIBits, LCS_sRGB, BITMAPINFO, BITMAPINFOHEADER,
-			BITMAPV5HEADER, BI_RGB, CBM_INIT, DIB_RGB_COLORS, LCS_GM_IMAGES, PROFILE_EMBEDDED,
-			PROFILE_LINKED, RGBQUAD,
+			CreateDIBitmap, DeleteObject, GetDIBits, GetObjectW, LCS_sRGB, RealizePalette,
+			SelectPalette, BITMAP, BITMAPINFO, BITMAPINFOHEADER, BITMAPV5HEADER, BI_BITFIELDS,
+			BI_RGB, CBM_INIT, DIB_RGB_COLORS, LCS_GM_IMAGES, PROFILE_EMBEDDED, PROFILE_LINKED,
+			RGBQUAD,
 		},
 		winnt::LONG,
-		winuser::{GetDC, SetClipboardData},
+		winuser::{GetClipboardData, GetDC, ReleaseDC, SetClipboardData},
 	},
 };
 
 use crate::common::{private, Error};
 
 #[cfg(feature = "image-data")]
-use crate::common::{ImageData, ScopeGuard};
+use crate::common::{
+	check_image_size, dpi_from_pixels_per_meter, ImageAttempts, ImageData, ImageMetadata,
+	ScopeGuard,
+};
 
+/// Allocates a global memory object holding `image` in the `CF_DIBV5` format.
+///
+/// This doesn't touch the clipboard, so it can be done before the clipboard gets emptied.
 #[cfg(feature = "image-data")]
-fn add_cf_dibv5(_open_clipboard: OpenClipboard, image: ImageData) -> Result<(), Error> {
+fn create_cf_dibv5(image: ImageData, premultiplied_alpha: bool) -> Result<HGLOBAL, Error> {
 	use std::intrinsics::copy_nonoverlapping;
-	use winapi::um::{
-		winbase::{GlobalAlloc, GHND},
-		wingdi::BI_BITFIELDS,
-		winuser::CF_DIBV5,
-	};
+	use winapi::um::winbase::{GlobalAlloc, GHND};
 
 	let header_size = size_of::<BITMAPV5HEADER>();
 	let header = BITMAPV5HEADER {
@@ -75,7 +81,11 @@ fn add_cf_dibv5(_open_clipboard: OpenClipboard, image: ImageData) -> Result<(),
 	// a negative height in the header, which according to the documentation, indicates that the
 	// image rows are in top-to-bottom order. HOWEVER: MS Word (and WordPad) cannot paste an image
 	// that has a negative height in its header.
-	let image = flip_v(image);
+	let mut image = flip_v(image);
+	// The header has no way of telling the two conventions apart, so the pixels are all there is.
+	if premultiplied_alpha {
+		premultiply_alpha(image.bytes.to_mut());
+	}
 
 	let data_size = header_size + image.bytes.len();
 	let hdata = unsafe { GlobalAlloc(GHND, data_size) };
@@ -90,6 +100,7 @@ fn add_cf_dibv5(_open_clipboard: OpenClipboard, image: ImageData) -> Result<(),
 	unsafe {
 		let data_ptr = GlobalLock(hdata) as *mut u8;
 		if data_ptr.is_null() {
+			GlobalFree(hdata);
 			return Err(Error::Unknown {
 				description: format!("Could not lock the global memory object at line {}", line!()),
 			});
@@ -116,15 +127,26 @@ fn add_cf_dibv5(_open_clipboard: OpenClipboard, image: ImageData) -> Result<(),
 		// If the non-allocating version of the function failed, we need to assign the new bytes to
 		// the global allocation.
 		if let Cow::Owned(new_pixels) = rgba_to_win(dst_pixels_slice) {
-			// SAFETY: `data_ptr` is valid to write to and has no outstanding mutable borrows, and
+			// SAFETY: `pixels_dst` is valid to write to and has no outstanding mutable borrows, and
 			// `new_pixels` will be the same length as the original bytes.
-			copy_nonoverlapping::<u8>(new_pixels.as_ptr(), data_ptr, new_pixels.len())
+			copy_nonoverlapping::<u8>(new_pixels.as_ptr(), pixels_dst, new_pixels.len())
 		}
 	}
 
+	Ok(hdata)
+}
+
+/// Places a memory object created by [`create_cf_dibv5`] onto the clipboard, which takes
+/// ownership of it.
+#[cfg(feature = "image-data")]
+fn add_cf_dibv5(open_clipboard: OpenClipboard, hdata: HGLOBAL) -> Result<(), Error> {
+	use winapi::um::winuser::CF_DIBV5;
+
 	unsafe {
+		// The system only takes ownership of the memory object if the call succeeds, so it has
+		// to be freed here in order to leave nothing behind for the next operation.
 		if SetClipboardData(CF_DIBV5, hdata as _).is_null() {
-			DeleteObject(hdata as _);
+			GlobalFree(hdata);
 			return Err(Error::Unknown {
 				description: format!(
 					"Call to `SetClipboardData` returned NULL at line {}",
@@ -134,23 +156,56 @@ fn add_cf_dibv5(_open_clipboard: OpenClipboard, image: ImageData) -> Result<(),
 		}
 	}
 
+	open_clipboard.close_after_write();
 	Ok(())
 }
 
 #[cfg(feature = "image-data")]
-fn read_cf_dibv5(dibv5: &[u8]) -> Result<ImageData<'static>, Error> {
+fn read_cf_dibv5(
+	dibv5: &[u8],
+	max_pixels: u64,
+) -> Result<(ImageData<'static>, ImageMetadata), Error> {
 	// The DIBV5 format is a BITMAPV5HEADER followed by the pixel data according to
 	// https://docs.microsoft.com/en-us/windows/win32/dataxchg/standard-clipboard-formats
 
 	// so first let's get a pointer to the header
 	let header_size = size_of::<BITMAPV5HEADER>();
 	if dibv5.len() < header_size {
-		return Err(Error::Unknown {
-			description: "When reading the DIBV5 data, it contained fewer bytes than the BITMAPV5HEADER size. This is invalid.".into()
+		return Err(Error::ImageDecode {
+			attempts: vec![("dib".into(), "the data is shorter than a BITMAPV5HEADER".into())],
 		});
 	}
 	let header = unsafe { &*(dibv5.as_ptr() as *const BITMAPV5HEADER) };
+	check_image_size(
+		header.bV5Width.unsigned_abs().into(),
+		header.bV5Height.unsigned_abs().into(),
+		max_pixels,
+	)?;
+
+	// The offset of an embedded profile is counted from the start of the header. Linked profiles
+	// only contain a file name, so they aren't reported.
+	let icc_profile = if header.bV5CSType as i32 == PROFILE_EMBEDDED {
+		let start = header.bV5ProfileData as usize;
+		dibv5.get(start..start.saturating_add(header.bV5ProfileSize as usize)).map(<[u8]>::to_vec)
+	} else {
+		None
+	};
+
+	// Negative resolutions are invalid, so they are treated just like unknown ones.
+	let metadata = ImageMetadata {
+		dpi: dpi_from_pixels_per_meter(
+			header.bV5XPelsPerMeter.try_into().unwrap_or(0),
+			header.bV5YPelsPerMeter.try_into().unwrap_or(0),
+		),
+		icc_profile,
+		..Default::default()
+	};
 
+	if let Some(image) = decode_dib(dibv5) {
+		return Ok((image, metadata));
+	}
+
+	// Other layouts, like palettes or compressed bitmaps, are converted by GDI.
 	let has_profile =
 		header.bV5CSType as i32 == PROFILE_LINKED || header.bV5CSType as i32 == PROFILE_EMBEDDED;
 
@@ -163,6 +218,9 @@ fn read_cf_dibv5(dibv5: &[u8]) -> Result<ImageData<'static>, Error> {
 	unsafe {
 		let image_bytes = dibv5.as_ptr().offset(pixel_data_start) as *const _;
 		let hdc = GetDC(std::ptr::null_mut());
+		let _release_dc = ScopeGuard::new(|| {
+			ReleaseDC(std::ptr::null_mut(), hdc);
+		});
 		let hbitmap = CreateDIBitmap(
 			hdc,
 			header as *const BITMAPV5HEADER as *const _,
@@ -178,6 +236,9 @@ fn read_cf_dibv5(dibv5: &[u8]) -> Result<ImageData<'static>, Error> {
 						.into(),
 			});
 		}
+		let _delete_bitmap = ScopeGuard::new(|| {
+			DeleteObject(hbitmap as _);
+		});
 		// Now extract the pixels in a desired format
 		let w = header.bV5Width;
 		let h = header.bV5Height.abs();
@@ -226,10 +287,183 @@ fn read_cf_dibv5(dibv5: &[u8]) -> Result<ImageData<'static>, Error> {
 
 		let result =
 			ImageData { bytes: Cow::Owned(result_bytes), width: w as usize, height: h as usize };
-		Ok(result)
+		Ok((result, metadata))
 	}
 }
 
+/// Decodes the pixels of a packed DIB without going through GDI, for the uncompressed layouts
+/// that images are commonly placed in: 24 and 32-bit RGB, and 16 and 32-bit bit fields.
+///
+/// Returns `None` for any other layout, like palettes or compressed bitmaps, and for data that
+/// doesn't contain all the pixels its header describes.
+#[cfg(feature = "image-data")]
+fn decode_dib(dib: &[u8]) -> Option<ImageData<'static>> {
+	let u16_at =
+		|offset: usize| Some(u16::from_le_bytes(dib.get(offset..offset + 2)?.try_into().ok()?));
+	let u32_at =
+		|offset: usize| Some(u32::from_le_bytes(dib.get(offset..offset + 4)?.try_into().ok()?));
+
+	// The fields of `BITMAPINFOHEADER`, which all of the later headers start with.
+	let header_size = u32_at(0)? as usize;
+	let width = u32_at(4)? as i32;
+	let height = u32_at(8)? as i32;
+	let bit_count = u16_at(14)?;
+	let compression = u32_at(16)?;
+	let colors_used = u32_at(32)? as usize;
+	if header_size < 40 || width <= 0 || height == 0 {
+		return None;
+	}
+
+	let (masks, masks_size) = match (compression, bit_count) {
+		(BI_RGB, 24) | (BI_RGB, 32) => ([0x00ff0000, 0x0000ff00, 0x000000ff, 0], 0),
+		(BI_RGB, 16) => ([0x7c00, 0x03e0, 0x001f, 0], 0),
+		(BI_BITFIELDS, 16) | (BI_BITFIELDS, 32) => {
+			// Later headers contain the masks, including one for alpha. They follow the plain
+			// `BITMAPINFOHEADER` otherwise.
+			let alpha_mask = if header_size >= 56 { u32_at(52)? } else { 0 };
+			(
+				[u32_at(40)?, u32_at(44)?, u32_at(48)?, alpha_mask],
+				if header_size == 40 { 12 } else { 0 },
+			)
+		}
+		_ => return None,
+	};
+
+	let mut pixels_start = header_size + masks_size + colors_used * 4;
+	// An embedded profile is usually placed after the pixels, but some applications put it
+	// right before them.
+	if header_size >= 124
+		&& u32_at(56)? as i32 == PROFILE_EMBEDDED
+		&& u32_at(112)? as usize == pixels_start
+	{
+		pixels_start += u32_at(116)? as usize;
+	}
+
+	let width = width as usize;
+	let rows = height.unsigned_abs() as usize;
+	let bytes_per_pixel = bit_count as usize / 8;
+	// Rows are padded to a multiple of 4 bytes.
+	let stride = (width * bytes_per_pixel + 3) & !3;
+	let pixels = dib.get(pixels_start..)?.get(..stride.checked_mul(rows)?)?;
+
+	let channel = |pixel: u32, mask: u32| {
+		let max = mask >> mask.trailing_zeros();
+		((pixel & mask) >> mask.trailing_zeros()) as u64 * 255 / max as u64
+	};
+	// The fourth byte of 32-bit RGB pixels is officially unused, but many applications store
+	// alpha in it. Where it's zero throughout, it can only be unused.
+	let alpha_in_unused_byte = compression == BI_RGB && bit_count == 32;
+	let mut alpha_seen = false;
+	let mut bytes = Vec::with_capacity(width * rows * 4);
+	for row in 0..rows {
+		// Rows are stored bottom-up, unless the height is negative.
+		let row = if height > 0 { rows - 1 - row } else { row };
+		for pixel in pixels[row * stride..][..width * bytes_per_pixel].chunks_exact(bytes_per_pixel)
+		{
+			let mut value = [0; 4];
+			value[..bytes_per_pixel].copy_from_slice(pixel);
+			let value = u32::from_le_bytes(value);
+			for &mask in &masks[..3] {
+				bytes.push(if mask == 0 { 0 } else { channel(value, mask) as u8 });
+			}
+			let alpha = match masks[3] {
+				0 if alpha_in_unused_byte => (value >> 24) as u8,
+				0 => 255,
+				mask => channel(value, mask) as u8,
+			};
+			alpha_seen |= alpha != 0;
+			bytes.push(alpha);
+		}
+	}
+	if alpha_in_unused_byte && !alpha_seen {
+		bytes.iter_mut().skip(3).step_by(4).for_each(|alpha| *alpha = 255);
+	}
+
+	Some(ImageData { width, height: rows, bytes: Cow::Owned(bytes) })
+}
+
+/// Reads the pixels of a device-dependent bitmap, as found on the clipboard under `CF_BITMAP`.
+///
+/// The colors of palette-based bitmaps are resolved through `hpalette`, which may be null. Unlike
+/// DIBs, these bitmaps don't have an alpha channel, so every pixel is returned as opaque.
+///
+/// Safety: `hbitmap` must be a valid bitmap handle, and `hpalette` either null or a valid palette
+/// handle.
+#[cfg(feature = "image-data")]
+unsafe fn read_cf_bitmap(
+	hbitmap: HBITMAP,
+	hpalette: HPALETTE,
+	max_pixels: u64,
+) -> Result<ImageData<'static>, Error> {
+	let mut bitmap: BITMAP = std::mem::zeroed();
+	let written =
+		GetObjectW(hbitmap as _, size_of::<BITMAP>() as i32, &mut bitmap as *mut BITMAP as *mut _);
+	if written == 0 {
+		return Err(Error::Unknown {
+			description: "Could not get the size of the bitmap, GetObjectW returned 0".into(),
+		});
+	}
+	let w = bitmap.bmWidth;
+	let h = bitmap.bmHeight.abs();
+	check_image_size(w.unsigned_abs().into(), h.unsigned_abs().into(), max_pixels)?;
+
+	let hdc = GetDC(std::ptr::null_mut());
+	let _release_dc = ScopeGuard::new(|| {
+		ReleaseDC(std::ptr::null_mut(), hdc);
+	});
+
+	let _restore_palette = if !hpalette.is_null() {
+		let previous_palette = SelectPalette(hdc, hpalette, FALSE);
+		RealizePalette(hdc);
+		Some(ScopeGuard::new(move || {
+			SelectPalette(hdc, previous_palette, FALSE);
+		}))
+	} else {
+		None
+	};
+
+	let mut output_header = BITMAPINFO {
+		bmiColors: [RGBQUAD { rgbRed: 0, rgbGreen: 0, rgbBlue: 0, rgbReserved: 0 }],
+		bmiHeader: BITMAPINFOHEADER {
+			biSize: size_of::<BITMAPINFOHEADER>() as u32,
+			biWidth: w,
+			biHeight: -h,
+			biBitCount: 32,
+			biPlanes: 1,
+			biCompression: BI_RGB,
+			biSizeImage: 0,
+			biXPelsPerMeter: 0,
+			biYPelsPerMeter: 0,
+			biClrUsed: 0,
+			biClrImportant: 0,
+		},
+	};
+
+	let mut bytes = vec![0u8; w as usize * h as usize * 4];
+	let result = GetDIBits(
+		hdc,
+		hbitmap,
+		0,
+		h as u32,
+		bytes.as_mut_ptr() as *mut _,
+		&mut output_header as *mut _,
+		DIB_RGB_COLORS,
+	);
+	if result == 0 {
+		return Err(Error::Unknown {
+			description: "Could not get the bitmap bits, GetDIBits returned 0".into(),
+		});
+	}
+
+	let mut bytes = win_to_rgba(&mut bytes);
+	// Whatever `GetDIBits` leaves in the place of the alpha channel is meaningless.
+	for pixel in bytes.chunks_exact_mut(4) {
+		pixel[3] = u8::MAX;
+	}
+
+	Ok(ImageData { bytes: Cow::Owned(bytes), width: w as usize, height: h as usize })
+}
+
 /// Converts the RGBA (u8) pixel data into the bitmap-native ARGB (u32) format in-place
 ///
 /// Safety: the `bytes` slice must have a length that's a multiple of 4
@@ -264,6 +498,20 @@ unsafe fn rgba_to_win(bytes: &mut [u8]) -> Cow<'_, [u8]> {
 	}
 }
 
+/// Multiplies the color channels of each pixel by its alpha value, in-place.
+///
+/// The alpha value is expected to be the last of every 4 bytes, so this works for both RGBA and
+/// BGRA pixels.
+#[cfg(feature = "image-data")]
+fn premultiply_alpha(bytes: &mut [u8]) {
+	for pixel in bytes.chunks_exact_mut(4) {
+		let alpha = u16::from(pixel[3]);
+		for channel in &mut pixel[..3] {
+			*channel = ((u16::from(*channel) * alpha + 127) / 255) as u8;
+		}
+	}
+}
+
 /// Vertically flips the image pixels in memory
 #[cfg(feature = "image-data")]
 fn flip_v(image: ImageData) -> ImageData<'static> {
@@ -358,7 +606,10 @@ unsafe fn convert_bytes_to_u32s(bytes: &mut [u8]) -> ImageDataCow<'_> {
 /// open at once, so we have to open it very sparingly or risk causing the rest
 /// of the system to be unresponsive. Instead, the clipboard is opened for
 /// every operation and then closed afterwards.
-pub(crate) struct Clipboard(());
+pub(crate) struct Clipboard {
+	/// The clipboard sequence number right after the last change made through this instance.
+	last_write_sequence: Option<u32>,
+}
 
 // The other platforms have `Drop` implementation on their
 // clipboard, so Windows should too for consistently.
@@ -371,14 +622,34 @@ struct OpenClipboard<'clipboard> {
 	// The Windows clipboard can not be sent between threads once
 	// open.
 	_marker: PhantomData<*const ()>,
-	_for_shim: &'clipboard mut Clipboard,
+	for_shim: &'clipboard mut Clipboard,
+}
+
+impl OpenClipboard<'_> {
+	/// Closes the clipboard after its contents were changed, remembering that the change was made
+	/// by this instance.
+	fn close_after_write(self) {
+		let OpenClipboard { _inner, for_shim, .. } = self;
+		// The sequence number is increased by `EmptyClipboard` and `SetClipboardData`, and has to
+		// be read while the clipboard is still open. Once it's closed, another application may
+		// change the contents before this runs.
+		for_shim.last_write_sequence =
+			Some(unsafe { winapi::um::winuser::GetClipboardSequenceNumber() });
+		drop(_inner);
+	}
 }
 
 impl Clipboard {
 	const DEFAULT_OPEN_ATTEMPTS: usize = 5;
 
 	pub(crate) fn new() -> Result<Self, Error> {
-		Ok(Self(()))
+		Ok(Self { last_write_sequence: None })
+	}
+
+	pub(crate) fn owner_is_self(&mut self) -> Result<bool, Error> {
+		// The clipboard is never opened with a window, so the owner can't be compared directly.
+		let current = unsafe { winapi::um::winuser::GetClipboardSequenceNumber() };
+		Ok(self.last_write_sequence == Some(current))
 	}
 
 	fn open(&mut self) -> Result<OpenClipboard, Error> {
@@ -406,7 +677,32 @@ impl Clipboard {
 		}
 		.map_err(|_| Error::ClipboardOccupied)?;
 
-		Ok(OpenClipboard { _inner: clipboard, _marker: PhantomData, _for_shim: self })
+		Ok(OpenClipboard { _inner: clipboard, _marker: PhantomData, for_shim: self })
+	}
+}
+
+/// The Windows clipboard, opened by arboard for the current thread.
+///
+/// While this exists, any of the native clipboard functions which require an open clipboard,
+/// like `GetClipboardData` or `EnumClipboardFormats`, may be called from the same thread. The
+/// clipboard is closed again once this is dropped, so `CloseClipboard` must never be called
+/// manually.
+pub struct RawClipboard<'clipboard> {
+	_open: OpenClipboard<'clipboard>,
+}
+
+/// Windows-specific extensions to the [`Clipboard`](crate::Clipboard) type.
+pub trait ClipboardExtWindows: private::Sealed {
+	/// Opens the clipboard so that native clipboard functions arboard doesn't expose can be
+	/// called, retrying the same way as all other operations do.
+	///
+	/// See [`RawClipboard`] for what is allowed while the clipboard is open.
+	fn open_raw(&mut self) -> Result<RawClipboard<'_>, Error>;
+}
+
+impl ClipboardExtWindows for crate::Clipboard {
+	fn open_raw(&mut self) -> Result<RawClipboard<'_>, Error> {
+		Ok(RawClipboard { _open: self.platform.open()? })
 	}
 }
 
@@ -429,66 +725,85 @@ impl<'clipboard> Get<'clipboard> {
 	}
 
 	pub(crate) fn text(self) -> Result<String, Error> {
-		const FORMAT: u32 = clipboard_win::formats::CF_UNICODETEXT;
+		let _clipboard_assertion = self.clipboard?;
+		read_unicode_text()
+	}
 
+	pub(crate) fn text_with_locale(self) -> Result<(String, Option<String>), Error> {
+		let _clipboard_assertion = self.clipboard?;
+		let text = read_unicode_text()?;
+		Ok((text, read_locale()))
+	}
+
+	pub(crate) fn raw(self, system_type: &str) -> Result<Vec<u8>, Error> {
 		let _clipboard_assertion = self.clipboard?;
 
-		// XXX: ToC/ToU race conditions are not possible because we are the sole owners of the clipboard currently.
-		if !clipboard_win::is_format_avail(FORMAT) {
+		let format = register_format(system_type)?;
+		if !clipboard_win::is_format_avail(format.get()) {
 			return Err(Error::ContentNotAvailable);
 		}
 
-		let text_size = clipboard_win::raw::size(FORMAT).ok_or_else(|| Error::Unknown {
-			description: "failed to read clipboard text size".into(),
-		})?;
-
-		// Allocate the specific number of WTF-16 characters we need to receive.
-		// This division is always accurate because Windows uses 16-bit characters.
-		let mut out: Vec<u16> = vec![0u16; text_size.get() / 2];
-
-		let bytes_read = {
-			// SAFETY: The source slice has a greater alignment than the resulting one.
-			let out: &mut [u8] =
-				unsafe { std::slice::from_raw_parts_mut(out.as_mut_ptr().cast(), out.len() * 2) };
-
-			let mut bytes_read = clipboard_win::raw::get(FORMAT, out).map_err(|_| {
-				Error::Unknown { description: "failed to read clipboard string".into() }
-			})?;
-
-			// Convert the number of bytes read to the number of `u16`s
-			bytes_read /= 2;
-
-			// Remove the NUL terminator, if it existed.
-			if let Some(last) = out.last().copied() {
-				if last == 0 {
-					bytes_read -= 1;
-				}
-			}
-
-			bytes_read
-		};
+		let mut data = Vec::new();
+		clipboard_win::raw::get_vec(format.get(), &mut data)
+			.map_err(|_| Error::Unknown { description: "failed to read clipboard data".into() })?;
+		Ok(data)
+	}
 
-		// Create a UTF-8 string from WTF-16 data, if it was valid.
-		String::from_utf16(&out[..bytes_read]).map_err(|_| Error::ConversionFailure)
+	#[cfg(feature = "image-data")]
+	pub(crate) fn image(self, max_pixels: u64) -> Result<ImageData<'static>, Error> {
+		self.image_with_metadata(max_pixels).map(|(image, _)| image)
 	}
 
 	#[cfg(feature = "image-data")]
-	pub(crate) fn image(self) -> Result<ImageData<'static>, Error> {
-		const FORMAT: u32 = clipboard_win::formats::CF_DIBV5;
+	pub(crate) fn image_with_metadata(
+		self,
+		max_pixels: u64,
+	) -> Result<(ImageData<'static>, ImageMetadata), Error> {
+		use clipboard_win::formats::{CF_BITMAP, CF_DIBV5, CF_PALETTE};
 
 		let _clipboard_assertion = self.clipboard?;
+		let mut attempts = ImageAttempts::default();
 
-		if !clipboard_win::is_format_avail(FORMAT) {
-			return Err(Error::ContentNotAvailable);
-		}
+		let dib = if clipboard_win::is_format_avail(CF_DIBV5) {
+			let mut data = Vec::new();
 
-		let mut data = Vec::new();
+			clipboard_win::raw::get_vec(CF_DIBV5, &mut data).map_err(|_| Error::Unknown {
+				description: "failed to read clipboard image data".into(),
+			})?;
 
-		clipboard_win::raw::get_vec(FORMAT, &mut data).map_err(|_| Error::Unknown {
-			description: "failed to read clipboard image data".into(),
-		})?;
+			read_cf_dibv5(&data, max_pixels)
+		} else {
+			Err(Error::ContentNotAvailable)
+		};
+		if let Some(image) = attempts.record("dib", dib)? {
+			return Ok(image);
+		}
+
+		// Windows usually synthesizes `CF_DIBV5` out of `CF_BITMAP`, but not in every case.
+		let bitmap = if clipboard_win::is_format_avail(CF_BITMAP) {
+			// The handles remain owned by the clipboard.
+			unsafe {
+				let hbitmap = GetClipboardData(CF_BITMAP);
+				if hbitmap.is_null() {
+					return Err(Error::Unknown {
+						description: "failed to read clipboard bitmap handle".into(),
+					});
+				}
+				let hpalette = if clipboard_win::is_format_avail(CF_PALETTE) {
+					GetClipboardData(CF_PALETTE)
+				} else {
+					std::ptr::null_mut()
+				};
 
-		read_cf_dibv5(&data)
+				read_cf_bitmap(hbitmap as HBITMAP, hpalette as HPALETTE, max_pixels)
+			}
+		} else {
+			Err(Error::ContentNotAvailable)
+		};
+		match attempts.record("bitmap", bitmap)? {
+			Some(image) => Ok((image, ImageMetadata::default())),
+			None => Err(attempts.into_error()),
+		}
 	}
 }
 
@@ -496,11 +811,21 @@ pub(crate) struct Set<'clipboard> {
 	clipboard: Result<OpenClipboard<'clipboard>, Error>,
 	exclude_from_cloud: bool,
 	exclude_from_history: bool,
+	premultiplied_alpha: bool,
+	html_document: bool,
+	locale: Option<u32>,
 }
 
 impl<'clipboard> Set<'clipboard> {
 	pub(crate) fn new(clipboard: &'clipboard mut Clipboard) -> Self {
-		Self { clipboard: clipboard.open(), exclude_from_cloud: false, exclude_from_history: false }
+		Self {
+			clipboard: clipboard.open(),
+			exclude_from_cloud: false,
+			exclude_from_history: false,
+			premultiplied_alpha: false,
+			html_document: false,
+			locale: None,
+		}
 	}
 
 	pub(crate) fn text(self, data: Cow<'_, str>) -> Result<(), Error> {
@@ -509,6 +834,9 @@ impl<'clipboard> Set<'clipboard> {
 		clipboard_win::raw::set_string(&data).map_err(|_| Error::Unknown {
 			description: "Could not place the specified text to the clipboard".into(),
 		})?;
+		if let Some(lcid) = self.locale {
+			write_locale(lcid)?;
+		}
 
 		add_clipboard_exclusions(open_clipboard, self.exclude_from_cloud, self.exclude_from_history)
 	}
@@ -516,6 +844,10 @@ impl<'clipboard> Set<'clipboard> {
 	pub(crate) fn html(self, html: Cow<'_, str>, alt: Option<Cow<'_, str>>) -> Result<(), Error> {
 		let open_clipboard = self.clipboard?;
 
+		// Register the format and build the document before `set_string` empties the clipboard.
+		let html_format = clipboard_win::register_format("HTML Format");
+		let html = wrap_html(&html, self.html_document);
+
 		let alt = match alt {
 			Some(s) => s.into(),
 			None => String::new(),
@@ -523,9 +855,11 @@ impl<'clipboard> Set<'clipboard> {
 		clipboard_win::raw::set_string(&alt).map_err(|_| Error::Unknown {
 			description: "Could not place the specified text to the clipboard".into(),
 		})?;
+		if let Some(lcid) = self.locale {
+			write_locale(lcid)?;
+		}
 
-		if let Some(format) = clipboard_win::register_format("HTML Format") {
-			let html = wrap_html(&html);
+		if let Some(format) = html_format {
 			clipboard_win::raw::set_without_clear(format.get(), html.as_bytes())
 				.map_err(|e| Error::Unknown { description: e.to_string() })?;
 		}
@@ -533,22 +867,50 @@ impl<'clipboard> Set<'clipboard> {
 		add_clipboard_exclusions(open_clipboard, self.exclude_from_cloud, self.exclude_from_history)
 	}
 
+	pub(crate) fn raw(self, system_type: &str, data: Vec<u8>) -> Result<(), Error> {
+		let open_clipboard = self.clipboard?;
+
+		let format = register_format(system_type)?;
+		clipboard_win::raw::set(format.get(), &data).map_err(|e| Error::Unknown {
+			description: format!("Failed to place the data on the clipboard: {}", e),
+		})?;
+
+		add_clipboard_exclusions(open_clipboard, self.exclude_from_cloud, self.exclude_from_history)
+	}
+
 	#[cfg(feature = "image-data")]
 	pub(crate) fn image(self, image: ImageData) -> Result<(), Error> {
 		let open_clipboard = self.clipboard?;
 
+		// Everything that can fail is prepared before emptying the clipboard so that a failure
+		// leaves the previous contents intact.
+		let hdata = create_cf_dibv5(image, self.premultiplied_alpha)?;
+
 		if let Err(e) = clipboard_win::raw::empty() {
+			unsafe { GlobalFree(hdata) };
 			return Err(Error::Unknown {
 				description: format!("Failed to empty the clipboard. Got error code: {}", e),
 			});
 		};
 
-		add_cf_dibv5(open_clipboard, image)
+		add_cf_dibv5(open_clipboard, hdata)
+	}
+}
+
+/// Registers the clipboard format named `system_type`, or looks it up if it already exists.
+fn register_format(system_type: &str) -> Result<NonZeroU32, Error> {
+	// Format names are stored as atoms, which have this maximum length.
+	const MAX_FORMAT_NAME_LEN: usize = 255;
+	if system_type.encode_utf16().count() > MAX_FORMAT_NAME_LEN {
+		return Err(Error::InvalidContentType);
 	}
+	clipboard_win::register_format(system_type).ok_or_else(|| Error::Unknown {
+		description: format!("failed to register the clipboard format {:?}", system_type),
+	})
 }
 
 fn add_clipboard_exclusions(
-	_open_clipboard: OpenClipboard<'_>,
+	open_clipboard: OpenClipboard<'_>,
 	exclude_from_cloud: bool,
 	exclude_from_history: bool,
 ) -> Result<(), Error> {
@@ -585,6 +947,7 @@ fn add_clipboard_exclusions(
 		}
 	}
 
+	open_clipboard.close_after_write();
 	Ok(())
 }
 
@@ -601,6 +964,31 @@ pub trait SetExtWindows: private::Sealed {
 	///
 	/// [clipboard history]: https://support.microsoft.com/en-us/windows/get-help-with-clipboard-30375039-ce71-9fe4-5b30-21b7aab6b13f
 	fn exclude_from_history(self) -> Self;
+
+	/// Writes images with their color channels premultiplied by alpha, instead of the straight
+	/// alpha that [`ImageData`](crate::ImageData) uses.
+	///
+	/// `CF_DIBV5` has no field saying which of the two conventions the pixels follow, so it's up to
+	/// the reading application to assume one. Some applications, such as those drawing the image
+	/// with `AlphaBlend`, expect premultiplied alpha and render semi-transparent pixels too bright
+	/// otherwise.
+	fn premultiplied_alpha(self) -> Self;
+
+	/// Makes [`Set::html`](crate::Set::html) take a complete HTML document, which is placed
+	/// as-is, instead of a fragment.
+	///
+	/// Applications paste the part of the document between the `<!--StartFragment-->` and
+	/// `<!--EndFragment-->` comments, or all of it if there are none. By default, the HTML is a
+	/// fragment, which gets wrapped into a minimal document with these comments.
+	fn html_document(self) -> Self;
+
+	/// Tags text with a locale by also writing `CF_LOCALE`, given as an LCID like `0x0409` for
+	/// `en-US`, or `None` for the calling thread's locale.
+	///
+	/// Applications that still read `CF_TEXT` get it converted from the Unicode text using the
+	/// code page of this locale. Without it, Windows picks the locale of the current keyboard
+	/// layout, which can turn non-ASCII characters into garbage when the two disagree.
+	fn text_locale(self, lcid: Option<u32>) -> Self;
 }
 
 impl SetExtWindows for crate::Set<'_> {
@@ -613,6 +1001,24 @@ impl SetExtWindows for crate::Set<'_> {
 		self.platform.exclude_from_history = true;
 		self
 	}
+
+	fn premultiplied_alpha(mut self) -> Self {
+		self.platform.premultiplied_alpha = true;
+		self
+	}
+
+	fn html_document(mut self) -> Self {
+		self.platform.html_document = true;
+		self
+	}
+
+	fn text_locale(mut self, lcid: Option<u32>) -> Self {
+		use winapi::um::winnls::GetThreadLocale;
+
+		// SAFETY: `GetThreadLocale` has no preconditions.
+		self.platform.locale = Some(lcid.unwrap_or_else(|| unsafe { GetThreadLocale() }));
+		self
+	}
 }
 
 pub(crate) struct Clear<'clipboard> {
@@ -625,63 +1031,340 @@ impl<'clipboard> Clear<'clipboard> {
 	}
 
 	pub(crate) fn clear(self) -> Result<(), Error> {
-		let _clipboard_assertion = self.clipboard?;
+		let open_clipboard = self.clipboard?;
 		clipboard_win::empty()
-			.map_err(|_| Error::Unknown { description: "failed to clear clipboard".into() })
-	}
-}
-
-fn wrap_html(ctn: &str) -> String {
-	let h_version = "Version:0.9";
-	let h_start_html = "\r\nStartHTML:";
-	let h_end_html = "\r\nEndHTML:";
-	let h_start_frag = "\r\nStartFragment:";
-	let h_end_frag = "\r\nEndFragment:";
-	let c_start_frag = "\r\n<html>\r\n<body>\r\n<!--StartFragment-->\r\n";
-	let c_end_frag = "\r\n<!--EndFragment-->\r\n</body>\r\n</html>";
-	let h_len = h_version.len()
-		+ h_start_html.len()
-		+ 10 + h_end_html.len()
-		+ 10 + h_start_frag.len()
-		+ 10 + h_end_frag.len()
-		+ 10;
-	let n_start_html = h_len + 2;
-	let n_start_frag = h_len + c_start_frag.len();
-	let n_end_frag = n_start_frag + ctn.len();
-	let n_end_html = n_end_frag + c_end_frag.len();
-	format!(
-		"{}{}{:010}{}{:010}{}{:010}{}{:010}{}{}{}",
-		h_version,
-		h_start_html,
-		n_start_html,
-		h_end_html,
-		n_end_html,
-		h_start_frag,
-		n_start_frag,
-		h_end_frag,
-		n_end_frag,
-		c_start_frag,
-		ctn,
-		c_end_frag,
-	)
-}
-
-#[cfg(all(test, feature = "image-data"))]
+			.map_err(|_| Error::Unknown { description: "failed to clear clipboard".into() })?;
+		open_clipboard.close_after_write();
+		Ok(())
+	}
+}
+
+/// Reads `CF_UNICODETEXT` from the clipboard, which must already be open.
+fn read_unicode_text() -> Result<String, Error> {
+	const FORMAT: u32 = clipboard_win::formats::CF_UNICODETEXT;
+
+	// XXX: ToC/ToU race conditions are not possible because we are the sole owners of the clipboard currently.
+	if !clipboard_win::is_format_avail(FORMAT) {
+		return Err(Error::ContentNotAvailable);
+	}
+
+	let text_size = clipboard_win::raw::size(FORMAT).ok_or_else(|| Error::Unknown {
+		description: "failed to read clipboard text size".into(),
+	})?;
+
+	// Allocate the specific number of WTF-16 characters we need to receive.
+	// This division is always accurate because Windows uses 16-bit characters.
+	let mut out: Vec<u16> = vec![0u16; text_size.get() / 2];
+
+	let bytes_read = {
+		// SAFETY: The source slice has a greater alignment than the resulting one.
+		let out: &mut [u8] =
+			unsafe { std::slice::from_raw_parts_mut(out.as_mut_ptr().cast(), out.len() * 2) };
+
+		let bytes_read = clipboard_win::raw::get(FORMAT, out).map_err(|_| Error::Unknown {
+			description: "failed to read clipboard string".into(),
+		})?;
+
+		// Convert the number of bytes read to the number of `u16`s
+		bytes_read / 2
+	};
+
+	// Create a UTF-8 string from WTF-16 data, if it was valid.
+	String::from_utf16(trim_nul_padding(&out[..bytes_read])).map_err(|_| Error::ConversionFailure)
+}
+
+/// Reads the locale of the clipboard's text as a language tag, like `en-US`, from `CF_LOCALE`.
+/// The clipboard must already be open.
+fn read_locale() -> Option<String> {
+	use winapi::um::{winnls::LCIDToLocaleName, winnt::LOCALE_NAME_MAX_LENGTH, winuser::CF_LOCALE};
+
+	if !clipboard_win::is_format_avail(CF_LOCALE) {
+		return None;
+	}
+
+	let mut lcid = [0u8; 4];
+	clipboard_win::raw::get(CF_LOCALE, &mut lcid).ok()?;
+	let lcid = u32::from_ne_bytes(lcid);
+
+	let mut name = [0u16; LOCALE_NAME_MAX_LENGTH];
+	// SAFETY: `name` is valid for writes of its whole length.
+	let len = unsafe { LCIDToLocaleName(lcid, name.as_mut_ptr(), name.len() as i32, 0) };
+	// The returned length includes the NUL terminator, and 0 indicates failure.
+	if len <= 1 {
+		return None;
+	}
+	String::from_utf16(&name[..len as usize - 1]).ok()
+}
+
+/// Places `lcid` onto the clipboard as `CF_LOCALE`, next to the text that was just set. The
+/// clipboard must already be open.
+fn write_locale(lcid: u32) -> Result<(), Error> {
+	use winapi::um::winuser::CF_LOCALE;
+
+	clipboard_win::raw::set_without_clear(CF_LOCALE, &lcid.to_ne_bytes()).map_err(|e| {
+		Error::Unknown { description: format!("Failed to place the text's locale: {}", e) }
+	})
+}
+
+/// Removes the NUL terminator from the end of text read from the clipboard, along with any extra
+/// NULs some applications pad their text with. NULs within the text are kept.
+fn trim_nul_padding(text: &[u16]) -> &[u16] {
+	let len = text.iter().rposition(|&c| c != 0).map_or(0, |last| last + 1);
+	&text[..len]
+}
+
+/// Builds the `CF_HTML` format out of `html`, which is a header holding the byte offsets of the
+/// document and of the fragment within it, followed by the document.
+///
+/// If `is_document` is set, `html` is a complete document, of which the part between the
+/// `<!--StartFragment-->` and `<!--EndFragment-->` comments, or else all of it, is the fragment.
+/// Otherwise `html` is the fragment, which gets wrapped into a minimal document.
+///
+/// See: https://learn.microsoft.com/en-us/windows/win32/dataxchg/html-clipboard-format
+fn wrap_html(html: &str, is_document: bool) -> String {
+	const START_FRAGMENT: &str = "<!--StartFragment-->";
+	const END_FRAGMENT: &str = "<!--EndFragment-->";
+
+	let (document, fragment) = if is_document {
+		let start = html.find(START_FRAGMENT).map_or(0, |i| i + START_FRAGMENT.len());
+		let end = html[start..].find(END_FRAGMENT).map_or(html.len(), |i| start + i);
+		(Cow::Borrowed(html), start..end)
+	} else {
+		let prefix = format!("<html>\r\n<body>\r\n{}\r\n", START_FRAGMENT);
+		let suffix = format!("\r\n{}\r\n</body>\r\n</html>", END_FRAGMENT);
+		let fragment = prefix.len()..prefix.len() + html.len();
+		(Cow::Owned(prefix + html + &suffix), fragment)
+	};
+
+	// All offsets are padded to the same number of digits, so the header's length is fixed. They
+	// count bytes from the start of the header.
+	let header = |start_html, end_html, start_fragment, end_fragment| {
+		format!(
+			"Version:0.9\r\nStartHTML:{:010}\r\nEndHTML:{:010}\r\nStartFragment:{:010}\r\nEndFragment:{:010}\r\n",
+			start_html, end_html, start_fragment, end_fragment,
+		)
+	};
+	let header_len = header(0, 0, 0, 0).len();
+	header(
+		header_len,
+		header_len + document.len(),
+		header_len + fragment.start,
+		header_len + fragment.end,
+	) + &document
+}
+
+#[cfg(test)]
 mod tests {
-	use super::{rgba_to_win, win_to_rgba};
+	#[cfg(feature = "image-data")]
+	use super::{decode_dib, premultiply_alpha, rgba_to_win, win_to_rgba};
+	use super::{trim_nul_padding, wrap_html};
 
+	#[cfg(feature = "image-data")]
 	const DATA: [u8; 16] =
 		[100, 100, 255, 100, 0, 0, 0, 255, 255, 100, 100, 255, 100, 255, 100, 100];
 
+	#[cfg(feature = "image-data")]
 	#[test]
 	fn check_win_to_rgba_conversion() {
 		let mut data = DATA;
 		unsafe { win_to_rgba(&mut data) };
 	}
 
+	#[cfg(feature = "image-data")]
 	#[test]
 	fn check_rgba_to_win_conversion() {
 		let mut data = DATA;
 		unsafe { rgba_to_win(&mut data) };
 	}
+
+	#[cfg(feature = "image-data")]
+	#[test]
+	fn check_alpha_premultiplication() {
+		let mut data = [255, 100, 0, 128, 255, 255, 255, 255, 200, 10, 90, 0];
+		premultiply_alpha(&mut data);
+		assert_eq!(data, [128, 50, 0, 128, 255, 255, 255, 255, 0, 0, 0, 0]);
+	}
+
+	/// Builds a packed DIB out of a header of `header_size` bytes and the pixel rows.
+	#[cfg(feature = "image-data")]
+	fn dib(
+		header_size: u32,
+		height: i32,
+		bit_count: u16,
+		compression: u32,
+		masks: &[u32],
+		rows: &[&[u8]],
+	) -> Vec<u8> {
+		let mut dib = vec![0; header_size as usize];
+		dib[..4].copy_from_slice(&header_size.to_le_bytes());
+		dib[4..8].copy_from_slice(&2i32.to_le_bytes());
+		dib[8..12].copy_from_slice(&height.to_le_bytes());
+		dib[12..14].copy_from_slice(&1u16.to_le_bytes());
+		dib[14..16].copy_from_slice(&bit_count.to_le_bytes());
+		dib[16..20].copy_from_slice(&compression.to_le_bytes());
+		for mask in masks {
+			dib.extend_from_slice(&mask.to_le_bytes());
+		}
+		if header_size > 40 {
+			let masks = dib.split_off(header_size as usize);
+			dib[40..40 + masks.len()].copy_from_slice(&masks);
+		}
+		rows.iter().for_each(|row| dib.extend_from_slice(row));
+		dib
+	}
+
+	#[cfg(feature = "image-data")]
+	#[test]
+	fn decode_dib_layouts() {
+		const RED_BLUE: [u8; 8] = [255, 0, 0, 255, 0, 0, 255, 255];
+		const GREEN_WHITE: [u8; 8] = [0, 255, 0, 255, 255, 255, 255, 255];
+		let expected = [RED_BLUE, GREEN_WHITE].concat();
+
+		// Bottom-up 24-bit rows, padded to 8 bytes.
+		let image = decode_dib(&dib(
+			40,
+			2,
+			24,
+			0,
+			&[],
+			&[&[0, 255, 0, 255, 255, 255, 0, 0], &[0, 0, 255, 255, 0, 0, 0, 0]],
+		))
+		.unwrap();
+		assert_eq!((image.width, image.height), (2, 2));
+		assert_eq!(*image.bytes, *expected);
+
+		// Top-down 32-bit rows without alpha are opaque.
+		let image = decode_dib(&dib(
+			40,
+			-2,
+			32,
+			0,
+			&[],
+			&[&[0, 0, 255, 0, 255, 0, 0, 0], &[0, 255, 0, 0, 255, 255, 255, 0]],
+		))
+		.unwrap();
+		assert_eq!(*image.bytes, *expected);
+
+		// Alpha in the otherwise unused byte is kept.
+		let image =
+			decode_dib(&dib(40, -1, 32, 0, &[], &[&[0, 0, 255, 128, 255, 0, 0, 0]])).unwrap();
+		assert_eq!(*image.bytes, [255, 0, 0, 128, 0, 0, 255, 0]);
+
+		// 5-6-5 bit fields following a plain header.
+		let image = decode_dib(&dib(
+			40,
+			-1,
+			16,
+			3,
+			&[0xf800, 0x07e0, 0x001f],
+			&[&[0x00, 0xf8, 0x1f, 0x00]],
+		))
+		.unwrap();
+		assert_eq!(*image.bytes, RED_BLUE);
+
+		// Bit fields with alpha in a V5 header.
+		let masks = [0x000000ff, 0x0000ff00, 0x00ff0000, 0xff000000];
+		let image =
+			decode_dib(&dib(124, -1, 32, 3, &masks, &[&[255, 0, 0, 64, 0, 0, 255, 0]])).unwrap();
+		assert_eq!(*image.bytes, [255, 0, 0, 64, 0, 0, 255, 0]);
+
+		// Palettes are left to GDI, as is data that's cut short.
+		assert!(decode_dib(&dib(40, 1, 8, 0, &[], &[&[0, 0, 0, 0]])).is_none());
+		assert!(decode_dib(&dib(40, 2, 24, 0, &[], &[&[0; 8]])).is_none());
+	}
+
+	#[cfg(feature = "image-data")]
+	#[test]
+	fn read_device_dependent_bitmaps() {
+		use super::read_cf_bitmap;
+		use crate::Error;
+		use winapi::um::wingdi::{
+			CreateBitmap, CreatePalette, DeleteObject, LOGPALETTE, PALETTEENTRY,
+		};
+
+		unsafe {
+			// Blue, green, red and an unused byte per pixel.
+			let pixels: [u8; 8] = [0, 0, 255, 0, 255, 0, 0, 0];
+			let hbitmap = CreateBitmap(2, 1, 1, 32, pixels.as_ptr() as *const _);
+			assert!(!hbitmap.is_null());
+			let image = read_cf_bitmap(hbitmap, std::ptr::null_mut(), u64::MAX).unwrap();
+			assert_eq!((image.width, image.height), (2, 1));
+			assert_eq!(*image.bytes, [255, 0, 0, 255, 0, 0, 255, 255]);
+			assert!(matches!(
+				read_cf_bitmap(hbitmap, std::ptr::null_mut(), 1),
+				Err(Error::TooLarge)
+			));
+			DeleteObject(hbitmap as _);
+
+			// `LOGPALETTE` ends in a variable length array, declared with a single entry.
+			#[repr(C)]
+			struct TwoColorPalette {
+				version: u16,
+				len: u16,
+				entries: [PALETTEENTRY; 2],
+			}
+			let entry = |red, green, blue| PALETTEENTRY {
+				peRed: red,
+				peGreen: green,
+				peBlue: blue,
+				peFlags: 0,
+			};
+			let palette = TwoColorPalette {
+				version: 0x300,
+				len: 2,
+				entries: [entry(0, 255, 0), entry(0, 0, 255)],
+			};
+			let hpalette = CreatePalette(&palette as *const TwoColorPalette as *const LOGPALETTE);
+			assert!(!hpalette.is_null());
+
+			// One palette index per pixel, with rows padded to two bytes.
+			let indices: [u8; 2] = [1, 0];
+			let hbitmap = CreateBitmap(2, 1, 1, 8, indices.as_ptr() as *const _);
+			assert!(!hbitmap.is_null());
+			let image = read_cf_bitmap(hbitmap, hpalette, u64::MAX).unwrap();
+			assert_eq!((image.width, image.height), (2, 1));
+			assert_eq!(*image.bytes, [0, 0, 255, 255, 0, 255, 0, 255]);
+			DeleteObject(hbitmap as _);
+			DeleteObject(hpalette as _);
+		}
+	}
+
+	#[test]
+	fn check_nul_padding_removal() {
+		let utf16 = |s: &str| s.encode_utf16().collect::<Vec<u16>>();
+
+		assert_eq!(trim_nul_padding(&utf16("abc\0")), utf16("abc"));
+		assert_eq!(trim_nul_padding(&utf16("abc\0\0\0")), utf16("abc"));
+		assert_eq!(trim_nul_padding(&utf16("a\0b\0")), utf16("a\0b"));
+		assert_eq!(trim_nul_padding(&utf16("abc")), utf16("abc"));
+		assert!(trim_nul_padding(&utf16("\0")).is_empty());
+	}
+
+	#[test]
+	fn check_html_wrapping() {
+		/// Returns the document and the fragment that the offsets in the header point to.
+		fn parse(cf_html: &str) -> (&str, &str) {
+			let offset = |name: &str| {
+				let start = cf_html.find(name).unwrap() + name.len();
+				cf_html[start..start + 10].parse::<usize>().unwrap()
+			};
+			(
+				&cf_html[offset("StartHTML:")..offset("EndHTML:")],
+				&cf_html[offset("StartFragment:")..offset("EndFragment:")],
+			)
+		}
+
+		// Offsets count bytes, not characters.
+		let fragment = "<b>größer als 🐔</b>";
+		let cf_html = wrap_html(fragment, false);
+		let (document, parsed_fragment) = parse(&cf_html);
+		assert_eq!(parsed_fragment, fragment);
+		assert!(document.starts_with("<html>") && document.ends_with("</html>"));
+		assert!(cf_html.ends_with(document));
+
+		let document = "<html><body>a<!--StartFragment--><i>ü</i><!--EndFragment-->b</body></html>";
+		assert_eq!(parse(&wrap_html(document, true)), (document, "<i>ü</i>"));
+		let document = "<html><body><i>ü</i></body></html>";
+		assert_eq!(parse(&wrap_html(document, true)), (document, document));
+	}
 }